@@ -83,7 +83,7 @@ resolve_delegated!(MyResolver, LogResolver => helper);
 
 resolve_instance!(MyResolver, Rc: MyCommand => MyCommand : MyCommand::new);
 
-#[allow(clippy::vtable_address_comparisons)]
+#[allow(ambiguous_wide_pointer_comparisons)]
 fn main() {
     let injector = Hanami::new(MyResolver {
         helper: LogResolver {},
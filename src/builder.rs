@@ -0,0 +1,176 @@
+//! Configuration of an injector before its first use
+
+use std::any::{type_name, TypeId};
+use std::time::Duration;
+
+use crate::inject::Hanami;
+use crate::resolve::{Lifetime, Provider, ResolvedBy, WiringError};
+use crate::typemap::type_key;
+
+/// Resolve an eager type in a new injector
+type Eager<R> = fn(&Hanami<R>) -> Result<(), WiringError>;
+
+/// Set an overridden provider in a new injector
+type Override<R> = Box<dyn FnOnce(&Hanami<R>) -> Result<(), WiringError>>;
+
+/// Function notified of the resolution of each provider
+type Listener = Box<dyn Fn(&ResolutionEvent) + Send + Sync>;
+
+/// Behaviour of an injector on cyclic dependencies
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CycleBehaviour {
+    /// Report a [WiringError::CyclicResolution] error: the fallible functions return it while
+    /// the other ones panic
+    #[default]
+    Error,
+    /// Panic on cyclic dependencies, including in the fallible functions, for example to abort
+    /// the tests of a misconfigured application
+    Panic,
+}
+
+/// Resolution of a provider, notified to the listeners of an injector
+#[derive(Debug)]
+pub enum ResolutionEvent<'e> {
+    /// The provider of a type was built
+    Resolved {
+        type_name: &'static str,
+        lifetime: Lifetime,
+        /// Time spent building the provider, including the resolution of its dependencies
+        duration: Duration,
+    },
+    /// The provider of a type could not be built
+    Failed {
+        type_name: &'static str,
+        error: &'e WiringError,
+    },
+}
+
+/// Options shared by an injector with its scopes, child injectors and [crate::Lazy] parameters
+#[derive(Default)]
+pub(crate) struct Config {
+    listeners: Vec<Listener>,
+    strict: bool,
+    cycles: CycleBehaviour,
+}
+
+impl Config {
+    pub(crate) fn notify(&self, event: ResolutionEvent) {
+        for listener in &self.listeners {
+            listener(&event);
+        }
+    }
+
+    /// Reject the providers overridden after building the injector in strict mode
+    pub(crate) fn check_override<T>(&self) -> Result<(), WiringError> {
+        if self.strict {
+            return Err(WiringError::StrictMode {
+                type_name: type_name::<T>(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Apply the cycle behaviour to the error of a resolution
+    pub(crate) fn check_error(&self, error: WiringError) -> WiringError {
+        match (&error, self.cycles) {
+            (WiringError::CyclicResolution { .. }, CycleBehaviour::Panic) => panic!("{}", error),
+            _ => error,
+        }
+    }
+}
+
+/// Builder of a [Hanami] injector.
+///
+/// The builder configures the injector before its first use, the injector can then be shared
+/// without requiring a mutable access:
+///
+/// * the providers overridden for some types, e.g. to inject mocks (see [Hanami::set_provider]).
+///   The overrides are validated against each other: a type can only be overridden once.
+/// * the eager singletons, which are constructed when the injector is built instead of on their first
+///   injection, for example a metrics exporter or a background scheduler which must run even if no other
+///   component depends on it.
+/// * the listeners notified of the resolution of each provider, for example to log the wiring.
+/// * the strict mode, which forbids overriding providers after building the injector.
+/// * the behaviour on cyclic dependencies, see [CycleBehaviour].
+pub struct HanamiBuilder<R> {
+    resolver: R,
+    overrides: Vec<(TypeId, &'static str, Override<R>)>,
+    eager: Vec<Eager<R>>,
+    config: Config,
+}
+
+impl<R: Send + Sync + 'static> HanamiBuilder<R> {
+    pub fn new(resolver: R) -> Self {
+        Self {
+            resolver,
+            overrides: Vec::new(),
+            eager: Vec::new(),
+            config: Config::default(),
+        }
+    }
+
+    /// Override the provider for the target type
+    pub fn provider<T: 'static + ResolvedBy<R>>(mut self, provider: Provider<T>) -> Self {
+        self.overrides.push((
+            type_key::<T>(),
+            type_name::<T>(),
+            Box::new(move |injector| injector.override_provider(provider)),
+        ));
+        self
+    }
+
+    /// Construct the target type when the injector is built.
+    ///
+    /// Eager types are resolved in the order of declaration, after their own dependencies.
+    /// Only the provider of the target type is resolved: the instances of on-demand bindings
+    /// are still created on each injection.
+    pub fn eager<T: 'static + ResolvedBy<R>>(mut self) -> Self {
+        self.eager
+            .push(|injector| injector.try_resolve::<T>().map(drop));
+        self
+    }
+
+    /// Notify a listener of the resolution of each provider.
+    ///
+    /// Listeners are called by the thread resolving the provider, after storing it: they can use the
+    /// injector but must not panic.
+    pub fn listener(mut self, listener: impl Fn(&ResolutionEvent) + Send + Sync + 'static) -> Self {
+        self.config.listeners.push(Box::new(listener));
+        self
+    }
+
+    /// Forbid overriding providers once the injector is built.
+    ///
+    /// [Hanami::set_provider] and [Hanami::replace_provider] then return a [WiringError::StrictMode] error,
+    /// including in the scopes and child injectors of the injector.
+    pub fn strict(mut self) -> Self {
+        self.config.strict = true;
+        self
+    }
+
+    /// Select the behaviour on cyclic dependencies
+    pub fn cycles(mut self, cycles: CycleBehaviour) -> Self {
+        self.config.cycles = cycles;
+        self
+    }
+
+    /// Build the injector, set the overridden providers and construct its eager types.
+    ///
+    /// Return an error if a type is overridden more than once, or the first error of an eager type
+    /// which could not be resolved
+    pub fn build(self) -> Result<Hanami<R>, WiringError> {
+        for (i, (tid, type_name, _)) in self.overrides.iter().enumerate() {
+            if self.overrides[..i].iter().any(|(t, _, _)| t == tid) {
+                return Err(WiringError::DuplicateOverride { type_name });
+            }
+        }
+        let injector = Hanami::with_config(self.resolver, self.config);
+        for (_, _, set) in self.overrides {
+            set(&injector)?;
+        }
+        for resolve in self.eager {
+            resolve(&injector)?;
+        }
+        Ok(injector)
+    }
+}
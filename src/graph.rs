@@ -0,0 +1,283 @@
+//! Dependency graph of the resolved types
+//!
+//! The injector records the direct dependencies of each provider while resolving it.
+//! [crate::Hanami::graph] returns a snapshot of these dependencies, for example to inspect the wiring
+//! of an application in tests or diagnostics.
+//!
+//! The graph can also be exported as a diagram, using the [Graphviz](https://graphviz.org) DOT language
+//! or [Mermaid](https://mermaid.js.org). The shape of the nodes depends on the lifetime of the types:
+//!
+//! | Lifetime      | DOT                  | Mermaid         |
+//! |---------------|----------------------|-----------------|
+//! | Singleton     | box                  | rectangle       |
+//! | Transient     | ellipse              | stadium         |
+//! | Scoped        | dashed box           | subroutine      |
+//! | ThreadLocal   | dotted box           | parallelogram   |
+//!
+//! Providers overridden using [crate::Hanami::set_provider] or [crate::Hanami::replace_provider] are filled in grey.
+//! The nodes are sorted by type name, the output does not depend on the resolution order and can be
+//! compared with a snapshot.
+//!
+//! For tooling, [Graph::to_json] produces a report of the resolved types, including their construction
+//! duration and the number of instances provided so far.
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use crate::resolve::Lifetime;
+use crate::typemap::{type_key, Entry};
+
+/// Resolved type of the dependency graph
+#[derive(Clone, Debug)]
+pub struct Node {
+    tid: TypeId,
+    type_name: &'static str,
+    lifetime: Lifetime,
+    overridden: bool,
+    dependencies: Vec<usize>,
+    duration: Option<Duration>,
+    provided: usize,
+}
+
+impl Node {
+    /// Name of the resolved type
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Lifetime of the instances provided for this type
+    pub fn lifetime(&self) -> Lifetime {
+        self.lifetime
+    }
+
+    /// Whether the provider was set by the user instead of a resolution rule
+    pub fn is_overridden(&self) -> bool {
+        self.overridden
+    }
+
+    /// Position of the direct dependencies of this type in the graph
+    pub fn dependencies(&self) -> &[usize] {
+        &self.dependencies
+    }
+
+    /// Time spent building the provider, including the resolution of its dependencies.
+    ///
+    /// Overridden providers are not built by the injector: their duration is unknown.
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Number of instances provided when the snapshot was taken
+    pub fn provided(&self) -> usize {
+        self.provided
+    }
+}
+
+/// Snapshot of the types resolved by an injector
+///
+/// The nodes are sorted in creation order: the providers of the dependencies of a type are created
+/// before its own provider. Overridden providers have no dependencies.
+#[derive(Clone, Debug, Default)]
+pub struct Graph {
+    nodes: Vec<Node>,
+}
+
+impl Graph {
+    pub(crate) fn new(entries: impl IntoIterator<Item = (TypeId, &'static str, Entry)>) -> Self {
+        let mut entries: Vec<_> = entries.into_iter().collect();
+        entries.sort_by_key(|(_, _, entry)| entry.order);
+        let positions: HashMap<_, _> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, (tid, _, _))| (*tid, i))
+            .collect();
+        let nodes = entries
+            .into_iter()
+            .map(|(tid, type_name, entry)| Node {
+                tid,
+                type_name,
+                lifetime: entry.lifetime,
+                overridden: entry.overridden,
+                dependencies: entry
+                    .deps
+                    .iter()
+                    .filter_map(|dep| positions.get(dep).copied())
+                    .collect(),
+                duration: entry.duration,
+                provided: entry.calls.load(Ordering::Relaxed),
+            })
+            .collect();
+        Self { nodes }
+    }
+
+    /// Resolved types in creation order
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Find the node of a resolved type
+    pub fn node<T: 'static>(&self) -> Option<&Node> {
+        let tid = type_key::<T>();
+        self.nodes.iter().find(|node| node.tid == tid)
+    }
+
+    /// Direct dependencies of a node
+    pub fn dependencies<'g>(&'g self, node: &'g Node) -> impl Iterator<Item = &'g Node> + 'g {
+        node.dependencies.iter().map(|i| &self.nodes[*i])
+    }
+
+    /// Render the graph in the Graphviz DOT language
+    pub fn to_dot(&self) -> String {
+        let (order, ids) = self.sorted();
+        let mut dot = String::from("digraph hanami {\n");
+        for &i in &order {
+            let node = &self.nodes[i];
+            let mut style = match node.lifetime {
+                Lifetime::Singleton | Lifetime::Transient => Vec::new(),
+                Lifetime::Scoped => vec!["dashed"],
+                Lifetime::ThreadLocal => vec!["dotted"],
+            };
+            if node.overridden {
+                style.push("filled");
+            }
+            let shape = match node.lifetime {
+                Lifetime::Transient => "ellipse",
+                _ => "box",
+            };
+            let _ = write!(
+                dot,
+                "    n{} [label=\"{}\", shape={}",
+                ids[i],
+                node.type_name.replace('\\', "\\\\").replace('"', "\\\""),
+                shape
+            );
+            if !style.is_empty() {
+                let _ = write!(dot, ", style=\"{}\"", style.join(","));
+            }
+            if node.overridden {
+                dot.push_str(", fillcolor=lightgrey");
+            }
+            dot.push_str("];\n");
+        }
+        for (from, to) in self.edges(&order, &ids) {
+            let _ = writeln!(dot, "    n{} -> n{};", from, to);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render the graph as a Mermaid flowchart
+    pub fn to_mermaid(&self) -> String {
+        let (order, ids) = self.sorted();
+        let mut mermaid = String::from("graph TD\n");
+        for &i in &order {
+            let node = &self.nodes[i];
+            let (open, close) = match node.lifetime {
+                Lifetime::Singleton => ("[", "]"),
+                Lifetime::Transient => ("([", "])"),
+                Lifetime::Scoped => ("[[", "]]"),
+                Lifetime::ThreadLocal => ("[/", "/]"),
+            };
+            let label = node
+                .type_name
+                .replace('"', "#quot;")
+                .replace('<', "#lt;")
+                .replace('>', "#gt;");
+            let _ = writeln!(mermaid, "    n{}{}\"{}\"{}", ids[i], open, label, close);
+        }
+        for (from, to) in self.edges(&order, &ids) {
+            let _ = writeln!(mermaid, "    n{} --> n{}", from, to);
+        }
+        let overridden: Vec<_> = order
+            .iter()
+            .filter(|i| self.nodes[**i].overridden)
+            .map(|i| format!("n{}", ids[*i]))
+            .collect();
+        if !overridden.is_empty() {
+            mermaid.push_str("    classDef overridden fill:#d3d3d3\n");
+            let _ = writeln!(mermaid, "    class {} overridden", overridden.join(","));
+        }
+        mermaid
+    }
+
+    /// Render a JSON report of the resolved types, sorted by type name.
+    ///
+    /// Each type lists its lifetime, its origin (`rule` or `override`), the names of its dependencies,
+    /// its construction duration in nanoseconds (`null` for overridden providers) and the number of
+    /// instances provided.
+    pub fn to_json(&self) -> String {
+        let (order, _) = self.sorted();
+        let mut types = Vec::new();
+        for i in order {
+            let node = &self.nodes[i];
+            let mut deps: Vec<_> = self.dependencies(node).map(|d| d.type_name).collect();
+            deps.sort_unstable();
+            let deps: Vec<_> = deps.into_iter().map(json_string).collect();
+            let origin = if node.overridden { "override" } else { "rule" };
+            let fields = [
+                ("type_name", json_string(node.type_name)),
+                ("lifetime", json_string(&format!("{:?}", node.lifetime))),
+                ("origin", json_string(origin)),
+                ("dependencies", format!("[{}]", deps.join(", "))),
+                (
+                    "duration_ns",
+                    node.duration
+                        .map_or("null".to_string(), |d| d.as_nanos().to_string()),
+                ),
+                ("provided", node.provided.to_string()),
+            ];
+            let fields: Vec<_> = fields
+                .iter()
+                .map(|(key, value)| format!("      \"{}\": {}", key, value))
+                .collect();
+            types.push(format!("    {{\n{}\n    }}", fields.join(",\n")));
+        }
+        if types.is_empty() {
+            return "{\n  \"types\": []\n}\n".to_string();
+        }
+        format!("{{\n  \"types\": [\n{}\n  ]\n}}\n", types.join(",\n"))
+    }
+
+    /// Positions of the nodes sorted by type name, and identifier of each node in this order
+    fn sorted(&self) -> (Vec<usize>, Vec<usize>) {
+        let mut order: Vec<_> = (0..self.nodes.len()).collect();
+        order.sort_by_key(|i| (self.nodes[*i].type_name, *i));
+        let mut ids = vec![0; order.len()];
+        for (id, i) in order.iter().enumerate() {
+            ids[*i] = id;
+        }
+        (order, ids)
+    }
+
+    /// Edges between the identifiers of the sorted nodes
+    fn edges(&self, order: &[usize], ids: &[usize]) -> Vec<(usize, usize)> {
+        let mut edges = Vec::new();
+        for &i in order {
+            let mut deps: Vec<_> = self.nodes[i].dependencies.iter().map(|d| ids[*d]).collect();
+            deps.sort_unstable();
+            edges.extend(deps.into_iter().map(|d| (ids[i], d)));
+        }
+        edges
+    }
+}
+
+/// Quote and escape a JSON string
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -1,23 +1,61 @@
-use std::any::{Any, TypeId};
-use std::collections::hash_map::{Entry, HashMap};
-use std::sync::{Arc, Mutex};
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use std::{mem, ptr};
 
+use crate::builder::{Config, ResolutionEvent};
+use crate::graph::Graph;
 use crate::resolve::*;
+use crate::typemap::{type_key, TypeMap};
 
 /// Dependency injection registry.
 ///
 /// This struct combines a [ProviderMap] with a resolver module.
 /// It can then resolve and inject all types resolved by the resolver module.
+///
+/// The resolver module must be [Send], [Sync] and `'static`: it is shared with the [Lazy] parameters
+/// created by the injector, which can be resolved by any thread, and with its scopes and child injectors.
+/// As all providers are also [Send] and [Sync], a single injector can be shared between threads,
+/// for example using an [Arc].
+/// Each resolved type has its own slot in the internal type map, which is only locked while
+/// looking up or inserting its provider: constructors and injected functions are called without
+/// holding any lock and independent types can be resolved in parallel.
+///
+/// An injector can also be configured before its first use with a [crate::HanamiBuilder].
 pub struct Hanami<R> {
-    tm: Mutex<TypeMap>,
-    resolver: R,
+    tm: Arc<TypeMap>,
+    resolver: Arc<R>,
+    scope: Option<Arc<TypeMap>>,
+    config: Arc<Config>,
 }
 
-impl<R> Hanami<R> {
+impl<R: Send + Sync + 'static> Hanami<R> {
     pub fn new(resolver: R) -> Self {
+        Self::with_config(resolver, Config::default())
+    }
+
+    pub(crate) fn with_config(resolver: R, config: Config) -> Self {
+        Self {
+            tm: Arc::default(),
+            resolver: Arc::new(resolver),
+            scope: None,
+            config: Arc::new(config),
+        }
+    }
+
+    /// Create a scope sharing the singletons of this injector.
+    ///
+    /// The scope has its own type map for the scoped bindings (see [crate::resolve_scoped]) and for the
+    /// providers depending on them: they are shared by all injections within the scope and dropped with it.
+    /// Scopes are not nested: a scope created from another scope only shares the singletons.
+    pub fn scope(&self) -> Self {
         Self {
-            tm: Mutex::default(),
-            resolver,
+            tm: self.tm.clone(),
+            resolver: self.resolver.clone(),
+            scope: Some(Arc::new(self.tm.scope())),
+            config: self.config.clone(),
         }
     }
 
@@ -27,104 +65,639 @@ impl<R> Hanami<R> {
 
     /// Obtain an instance of the target type.
     ///
-    /// Return an error if the type could not be resolved
+    /// Panic if the type could not be resolved, see [Hanami::try_inject]
     pub fn inject<T: 'static + ResolvedBy<R>>(&self) -> T {
-        self.tm
-            .lock()
-            .unwrap()
-            .resolve_with(&self.resolver)
-            .provide()
+        match self.try_inject() {
+            Ok(t) => t,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Obtain an instance of the target type.
+    ///
+    /// Return an error if the type could not be resolved
+    pub fn try_inject<T: 'static + ResolvedBy<R>>(&self) -> Result<T, WiringError> {
+        self.try_resolve::<T>()?.try_provide()
+    }
+
+    /// Look up or build the provider of the target type, without providing an instance
+    pub(crate) fn try_resolve<T: 'static + ResolvedBy<R>>(
+        &self,
+    ) -> Result<Provider<T>, WiringError> {
+        self.resolution()
+            .try_resolve_with(self.resolver.as_ref())
+            .map_err(|e| self.config.check_error(e))
+    }
+
+    /// Create a child injector inheriting the providers already resolved by this injector.
+    ///
+    /// Providers can be overridden in the child using [Hanami::set_provider], for example to inject mocks
+    /// in tests without building the whole graph again. The child reuses the providers of its parent,
+    /// unless they depend (directly or transitively) on an overridden type: these providers are resolved
+    /// again in the child only. The child of a scope does not inherit its scoped bindings.
+    pub fn child(&self) -> Self {
+        Self {
+            tm: Arc::new(TypeMap::child(self.tm.clone())),
+            resolver: self.resolver.clone(),
+            scope: None,
+            config: self.config.clone(),
+        }
     }
 
     /// Override the provider for the target type.
     ///
-    /// Return an error if the type has already been resolved
+    /// The provider of a scope is only used within this scope, it is then handled as a scoped binding.
+    /// Scoped bindings (see [crate::resolve_scoped]) can only be overridden in a scope.
+    /// Return an error if the type has already been resolved, or in strict mode
     pub fn set_provider<T>(&mut self, provider: Provider<T>) -> Result<(), WiringError>
     where
         T: 'static + ResolvedBy<R>,
     {
-        let mut tm = self.tm.lock().unwrap();
-        if tm.get_provider::<T>().is_some() {
-            return Err(WiringError::AlreadyResolved);
+        self.config.check_override::<T>()?;
+        self.override_provider(provider)
+    }
+
+    /// Override the provider for the target type, regardless of the strict mode
+    pub(crate) fn override_provider<T>(&self, provider: Provider<T>) -> Result<(), WiringError>
+    where
+        T: 'static + ResolvedBy<R>,
+    {
+        self.override_map::<T>()?
+            .slot::<T>()
+            .set_if_vacant(provider, <T as ResolvedBy<R>>::LIFETIME)
+    }
+
+    /// Type map storing the overridden providers: scoped bindings can only be overridden in a scope
+    fn override_map<T>(&self) -> Result<&TypeMap, WiringError>
+    where
+        T: 'static + ResolvedBy<R>,
+    {
+        match &self.scope {
+            Some(scope) => Ok(scope),
+            None if <T as ResolvedBy<R>>::LIFETIME == Lifetime::Scoped => {
+                Err(WiringError::ScopedOverride {
+                    type_name: type_name::<T>(),
+                })
+            }
+            None => Ok(&self.tm),
         }
-        tm.set_if_vacant::<Provider<T>>(TypeMapEntry::Ready(Box::new(provider)));
-        Ok(())
+    }
+
+    /// Replace the provider for the target type, even if it has already been resolved.
+    ///
+    /// All the providers which depend (directly or transitively) on the target type are discarded and
+    /// built again on their next injection, for example to reload a configuration. Instances which were
+    /// already injected, including singletons, are not affected, and neither are the providers already
+    /// resolved by existing scopes, child injectors and [Lazy] parameters.
+    /// A provider which was being built while its dependency was replaced is used once but not stored.
+    /// As for [Hanami::set_provider], the provider of a scope is only used within this scope.
+    ///
+    /// Return an error if the target type is being resolved by the current thread, or in strict mode
+    pub fn replace_provider<T>(&self, provider: Provider<T>) -> Result<(), WiringError>
+    where
+        T: 'static + ResolvedBy<R>,
+    {
+        self.config.check_override::<T>()?;
+        self.override_map::<T>()?
+            .replace(provider, <T as ResolvedBy<R>>::LIFETIME)
+    }
+
+    /// Snapshot of the types resolved by this injector and of their dependencies.
+    ///
+    /// The graph of a scope also includes its scoped bindings.
+    pub fn graph(&self) -> Graph {
+        let mut entries: HashMap<_, _> = self
+            .tm
+            .entries()
+            .into_iter()
+            .map(|(tid, name, entry)| (tid, (name, entry)))
+            .collect();
+        if let Some(scope) = &self.scope {
+            entries.extend(
+                scope
+                    .entries()
+                    .into_iter()
+                    .map(|(tid, name, entry)| (tid, (name, entry))),
+            );
+        }
+        Graph::new(
+            entries
+                .into_iter()
+                .map(|(tid, (name, entry))| (tid, name, entry)),
+        )
+    }
+
+    /// Render the dependency graph in the Graphviz DOT language, see [crate::graph]
+    pub fn export_dot(&self) -> String {
+        self.graph().to_dot()
+    }
+
+    /// Render the dependency graph as a Mermaid flowchart, see [crate::graph]
+    pub fn export_mermaid(&self) -> String {
+        self.graph().to_mermaid()
+    }
+
+    /// Render a JSON report of the resolved types, see [crate::graph::Graph::to_json]
+    pub fn export_json(&self) -> String {
+        self.graph().to_json()
     }
 
     /// Call a function after injecting all its parameters
+    ///
+    /// Panic if a parameter could not be resolved, see [Hanami::try_inject_and_call]
     pub fn inject_and_call<F, I, O>(&self, f: F) -> O
     where
         I: Injectable<R>,
         F: Callable<I, O>,
     {
-        let mut tm = self.tm.lock().unwrap();
-        tm.inject_and_call(&self.resolver, f)
+        match self.try_inject_and_call(f) {
+            Ok(o) => o,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Call a function after injecting all its parameters
+    ///
+    /// The function is called without holding any lock: it can use the injector itself.
+    /// Return an error if a parameter could not be resolved
+    pub fn try_inject_and_call<F, I, O>(&self, f: F) -> Result<O, WiringError>
+    where
+        I: Injectable<R>,
+        F: Callable<I, O>,
+    {
+        self.resolution()
+            .try_inject_and_call(self.resolver.as_ref(), f)
+            .map_err(|e| self.config.check_error(e))
+    }
+
+    /// Start a new resolution context on the shared type map
+    fn resolution(&self) -> Resolution {
+        Resolution::new(
+            self.tm.clone(),
+            self.resolver.clone(),
+            self.scope.clone(),
+            Vec::new(),
+            self.config.clone(),
+        )
     }
 }
 
-#[derive(Debug)]
-enum TypeMapEntry {
-    Resolving,
-    Ready(Box<dyn Any>),
+impl<R: Roots + Send + Sync + 'static> Hanami<R> {
+    /// Resolve the providers of all root types declared with [crate::roots], for example to build
+    /// the singletons of the application on startup instead of during the first request.
+    ///
+    /// Return the errors of all the root types which could not be resolved
+    pub fn warm_up(&self) -> Vec<WiringError> {
+        self.check_roots(false)
+    }
+
+    /// Resolve all root types declared with [crate::roots] and provide an instance of each of them.
+    ///
+    /// Unlike [Hanami::warm_up], the constructors of on-demand instances are also called:
+    /// the provided instances are dropped immediately. A panicking constructor is reported as a
+    /// [WiringError::ConstructorPanicked] error.
+    /// Return the errors of all the root types which could not be resolved or provided
+    pub fn validate(&self) -> Vec<WiringError> {
+        self.check_roots(true)
+    }
+
+    fn check_roots(&self, provide: bool) -> Vec<WiringError> {
+        let mut check = RootCheck {
+            injector: self,
+            provide,
+            errors: Vec::new(),
+        };
+        R::visit_roots(&mut check);
+        check.errors
+    }
 }
 
-enum TypeMapContent<'a, T> {
-    None,
-    Resolving,
-    Mismatch,
-    Ready(&'a T),
+/// Resolve each root type of an injector, collecting the errors
+struct RootCheck<'i, R> {
+    injector: &'i Hanami<R>,
+    provide: bool,
+    errors: Vec<WiringError>,
 }
 
-/// Store singletons of [Any] type
-#[derive(Default)]
-struct TypeMap(HashMap<TypeId, TypeMapEntry>);
-impl TypeMap {
-    /// Retrieve a stored singleton if it exists
-    fn get<T: Any>(&self) -> TypeMapContent<T> {
-        match self.0.get(&TypeId::of::<T>()) {
-            None => TypeMapContent::None,
-            Some(TypeMapEntry::Resolving) => TypeMapContent::Resolving,
-            Some(TypeMapEntry::Ready(b)) => match b.downcast_ref::<T>() {
-                None => TypeMapContent::Mismatch,
-                Some(b) => TypeMapContent::Ready(b),
-            },
+impl<R: Send + Sync + 'static> VisitRoot<R> for RootCheck<'_, R> {
+    fn visit<T: ResolvedBy<R> + 'static>(&mut self) {
+        let provided = self.injector.try_resolve::<T>().and_then(|provider| {
+            if !self.provide {
+                return Ok(());
+            }
+            panic::catch_unwind(AssertUnwindSafe(|| provider.try_provide().map(drop)))
+                .unwrap_or_else(|payload| {
+                    Err(WiringError::ConstructorPanicked {
+                        type_name: type_name::<T>(),
+                        message: panic_message(payload.as_ref()),
+                    })
+                })
+        });
+        self.errors.extend(provided.err());
+    }
+}
+
+/// Deferred injection of a target type.
+///
+/// A lazy parameter captures the resolution rule of the target type without resolving it:
+/// the provider is only built on the first call to [Lazy::get], expensive components are then
+/// only constructed when they are actually used. As the target type is not resolved while building
+/// the component which receives the lazy parameter, lazy parameters can also break construction
+/// cycles between singletons.
+///
+/// A lazy parameter created by a submodule resolves the target type with the rules of this submodule,
+/// or with the rule of a parent module which overrides it (see [crate::resolve_delegated]).
+///
+/// Clones of a lazy parameter share the same provider. Lazy singletons are built once, while lazy
+/// on-demand instances are created on each call. A lazy parameter can outlive its injector but
+/// the target type can then no longer be resolved.
+///
+/// Lazy parameters are always resolved outside of any scope: they can not target scoped bindings.
+pub struct Lazy<T> {
+    inner: Arc<LazyInner<T>>,
+}
+
+type LazyResolve<T> = dyn Fn() -> Result<Provider<T>, WiringError> + Send + Sync;
+
+struct LazyInner<T> {
+    resolve: Box<LazyResolve<T>>,
+    provider: OnceLock<Provider<T>>,
+}
+
+impl<T> Clone for Lazy<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Lazy<T> {
+    fn new(resolve: Box<LazyResolve<T>>) -> Self {
+        Self {
+            inner: Arc::new(LazyInner {
+                resolve,
+                provider: OnceLock::new(),
+            }),
+        }
+    }
+
+    /// Obtain an instance of the target type, resolving it on the first call.
+    ///
+    /// Panic if the type could not be resolved, see [Lazy::try_get]
+    pub fn get(&self) -> T {
+        match self.try_get() {
+            Ok(t) => t,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Obtain an instance of the target type, resolving it on the first call.
+    ///
+    /// Return an error if the type could not be resolved. A failed resolution is retried by the next call.
+    pub fn try_get(&self) -> Result<T, WiringError> {
+        let provider = match self.inner.provider.get() {
+            Some(p) => p,
+            None => {
+                let p = (self.inner.resolve)()?;
+                self.inner.provider.get_or_init(|| p)
+            }
+        };
+        provider.try_provide()
+    }
+}
+
+/// Extract the message of a caught panic
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+/// Find a submodule in a parent resolver module
+trait Project: Send + Sync {
+    fn project<'r>(&self, resolver: &'r dyn Any) -> Option<&'r dyn Any>;
+}
+
+struct Projection<P, S>(fn(&P) -> &S);
+
+impl<P: 'static, S: 'static> Project for Projection<P, S> {
+    fn project<'r>(&self, resolver: &'r dyn Any) -> Option<&'r dyn Any> {
+        resolver
+            .downcast_ref::<P>()
+            .map(|p| (self.0)(p) as &dyn Any)
+    }
+}
+
+/// Resolve a type with the rule of the parent module which overrides it, see [ProviderMap::override_with]
+type ResolveOverride = fn(&dyn Any, &mut Resolution) -> Result<Box<dyn Any>, WiringError>;
+
+fn resolve_override<P: 'static, T: ResolvedBy<P> + 'static>(
+    parent: &dyn Any,
+    resolution: &mut Resolution,
+) -> Result<Box<dyn Any>, WiringError> {
+    let parent = parent
+        .downcast_ref::<P>()
+        .ok_or(WiringError::TypeMismatch {
+            type_name: type_name::<P>(),
+        })?;
+    let p = resolution.try_resolve_with::<P, T>(parent)?;
+    Ok(Box::new(p))
+}
+
+/// Step of the path from the root resolver module to a submodule
+#[derive(Clone)]
+struct Layer {
+    project: Arc<dyn Project>,
+    /// Types resolved by the parent module instead of the submodule
+    overrides: Vec<(TypeId, ResolveOverride)>,
+}
+
+impl Layer {
+    fn overriding(&self, key: TypeId) -> Option<ResolveOverride> {
+        self.overrides
+            .iter()
+            .find(|(t, _)| *t == key)
+            .map(|(_, resolve)| *resolve)
+    }
+}
+
+/// Find the submodule at the end of a path, starting from the root resolver module
+fn project<'r>(root: &'r dyn Any, path: &[Layer]) -> Option<&'r dyn Any> {
+    path.iter()
+        .try_fold(root, |resolver, layer| layer.project.project(resolver))
+}
+
+/// Resolution context
+///
+/// A new context is used for each injection: it looks up or claims the slots of the shared
+/// type map and tracks the stack of types which are currently being resolved by this thread
+/// to report the full dependency chain of cyclical resolutions.
+///
+/// It also tracks the path from the root resolver module to the submodule of the current rule,
+/// which enables [Lazy] parameters to find this submodule when they are resolved later,
+/// and the types overridden by each parent module along this path.
+///
+/// In a scope, it tracks whether the type being resolved depends on scoped providers:
+/// such providers are stored in the type map of the scope instead of the shared type map.
+struct Resolution {
+    tm: Arc<TypeMap>,
+    root: Arc<dyn Any + Send + Sync>,
+    scope: Option<Arc<TypeMap>>,
+    path: Vec<Layer>,
+    stack: Vec<(TypeId, &'static str)>,
+    /// Direct dependencies of each type of the stack
+    deps: Vec<Vec<TypeId>>,
+    scoped: bool,
+    config: Arc<Config>,
+}
+
+impl Resolution {
+    fn new(
+        tm: Arc<TypeMap>,
+        root: Arc<dyn Any + Send + Sync>,
+        scope: Option<Arc<TypeMap>>,
+        path: Vec<Layer>,
+        config: Arc<Config>,
+    ) -> Self {
+        Self {
+            tm,
+            root,
+            scope,
+            path,
+            stack: Vec::new(),
+            deps: Vec::new(),
+            scoped: false,
+            config,
         }
     }
 
-    fn get_provider<T: 'static>(&self) -> Option<&Provider<T>> {
-        match self.get::<Provider<T>>() {
-            TypeMapContent::Ready(v) => Some(v),
-            _ => None,
+    /// Look up or build the provider of the target type in the type map of the scope or in the shared one
+    fn lookup<R, T: ResolvedBy<R> + 'static>(
+        &mut self,
+        resolver: &R,
+    ) -> Result<Provider<T>, WiringError> {
+        let scoped_rule = <T as ResolvedBy<R>>::LIFETIME == Lifetime::Scoped;
+        match self.scope.clone() {
+            Some(scope) => {
+                if let Some(p) = scope.slot::<T>().get::<T>(T::module())? {
+                    self.scoped = true;
+                    return Ok(p);
+                }
+                if scoped_rule {
+                    let p = self.resolve_in(&scope, resolver)?;
+                    self.scoped = true;
+                    return Ok(p);
+                }
+            }
+            None if scoped_rule => {
+                return Err(WiringError::OutOfScope {
+                    type_name: type_name::<T>(),
+                })
+            }
+            None => (),
         }
+        let tm = self.tm.clone();
+        self.resolve_in(&tm, resolver)
     }
 
-    /// Fill a free spot
-    fn set_if_vacant<T: Any>(&mut self, data: TypeMapEntry) {
-        let Entry::Vacant(v) = self.0.entry(TypeId::of::<T>()) else {
-            // TODO: extra work to detect cyclical dependencies?
-            panic!("Entry is not vacant");
+    /// Look up or build the provider of the target type in one of the type maps
+    fn resolve_in<R, T: ResolvedBy<R> + 'static>(
+        &mut self,
+        tm: &TypeMap,
+        resolver: &R,
+    ) -> Result<Provider<T>, WiringError> {
+        let slot = tm.slot::<T>();
+        if let Some(p) = tm.claim::<T>(&slot, &self.stack, T::module())? {
+            return Ok(p);
+        }
+
+        // A panicking constructor is reported as an error: the partially built provider is
+        // discarded and the slot is released, the injector remains usable and can retry later.
+        let outer = mem::replace(&mut self.scoped, false);
+        let depth = self.path.len();
+        self.stack.push((slot.tid(), slot.type_name()));
+        self.deps.push(Vec::new());
+        let generation = tm.generation();
+        let start = Instant::now();
+        let built = panic::catch_unwind(AssertUnwindSafe(|| T::build_provider(resolver, self)))
+            .unwrap_or_else(|payload| {
+                Err(WiringError::ConstructorPanicked {
+                    type_name: slot.type_name(),
+                    message: panic_message(payload.as_ref()),
+                })
+            });
+        let duration = start.elapsed();
+        self.stack.pop();
+        let deps = self.deps.pop().unwrap_or_default();
+        self.path.truncate(depth);
+        let scoped = mem::replace(&mut self.scoped, outer);
+        let in_scope = self.scope.as_deref().is_some_and(|s| ptr::eq(s, tm));
+        // A provider replaced during the construction may be a dependency: the built provider is then
+        // used by this resolution only and built again by the next one
+        let current = tm.is_current(generation);
+        let result = match built {
+            Ok(p) if scoped && !in_scope => {
+                // Providers depending on scoped bindings are moved to the scope
+                slot.release();
+                self.scoped = true;
+                match (&self.scope, <T as ResolvedBy<R>>::LIFETIME) {
+                    (Some(scope), Lifetime::Transient) if current => scope.slot::<T>().insert(
+                        p,
+                        deps,
+                        Lifetime::Transient,
+                        duration,
+                        T::module(),
+                    ),
+                    (Some(_), Lifetime::Transient) => Ok(p),
+                    _ => Err(WiringError::ScopedDependency {
+                        type_name: slot.type_name(),
+                    }),
+                }
+            }
+            Ok(p) if !current => {
+                slot.release();
+                self.scoped |= scoped;
+                Ok(p)
+            }
+            Ok(p) => {
+                let p = slot.fill(
+                    p,
+                    deps,
+                    <T as ResolvedBy<R>>::LIFETIME,
+                    duration,
+                    T::module(),
+                );
+                self.scoped |= scoped;
+                Ok(p)
+            }
+            Err(e) => {
+                slot.release();
+                Err(e)
+            }
         };
-        v.insert(data);
+        self.config.notify(match &result {
+            Ok(_) => ResolutionEvent::Resolved {
+                type_name: slot.type_name(),
+                lifetime: <T as ResolvedBy<R>>::LIFETIME,
+                duration,
+            },
+            Err(error) => ResolutionEvent::Failed {
+                type_name: slot.type_name(),
+                error,
+            },
+        });
+        result
     }
 
-    /// Fill a resolving spot
-    fn set_if_resolving<T: Any>(&mut self, data: TypeMapEntry) {
-        let Entry::Occupied(mut o) = self.0.entry(TypeId::of::<T>()) else {
-            panic!("Entry is not occupied");
+    /// Resolve a type overridden by the parent module of a layer with the rule of this parent.
+    ///
+    /// The path is shortened to the parent module during the resolution
+    fn resolve_override<T: 'static>(
+        &mut self,
+        depth: usize,
+        resolve: ResolveOverride,
+    ) -> Result<Provider<T>, WiringError> {
+        let mismatch = || WiringError::TypeMismatch {
+            type_name: type_name::<T>(),
         };
-        // Check the occupied status
-        o.insert(data);
+        let inner = self.path.split_off(depth);
+        let root = self.root.clone();
+        let resolved = project(root.as_ref(), &self.path)
+            .ok_or_else(mismatch)
+            .and_then(|parent| resolve(parent, self));
+        self.path.truncate(depth);
+        self.path.extend(inner);
+        resolved?
+            .downcast::<Provider<T>>()
+            .map(|p| *p)
+            .map_err(|_| mismatch())
     }
 }
 
-impl ProviderMap for TypeMap {
-    fn resolve_with<R, T: ResolvedBy<R> + 'static>(&mut self, resolver: &R) -> &Provider<T> {
-        if self.get_provider::<T>().is_none() {
-            self.set_if_vacant::<Provider<T>>(TypeMapEntry::Resolving);
-            let p = T::build_provider(resolver, self);
-            self.set_if_resolving::<Provider<T>>(TypeMapEntry::Ready(Box::new(p)));
+impl ProviderMap for Resolution {
+    fn try_resolve_with<R, T: ResolvedBy<R> + 'static>(
+        &mut self,
+        resolver: &R,
+    ) -> Result<Provider<T>, WiringError> {
+        // A type overridden by a parent module is never resolved by the rule of the submodule
+        let key = type_key::<T>();
+        let overriding = self
+            .path
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, layer)| layer.overriding(key).map(|resolve| (depth, resolve)));
+        let p = match overriding {
+            Some((depth, resolve)) => self.resolve_override::<T>(depth, resolve)?,
+            None => self.lookup::<R, T>(resolver)?,
+        };
+        // Record the dependency of the type being resolved
+        if let Some(deps) = self.deps.last_mut().filter(|deps| !deps.contains(&key)) {
+            deps.push(key);
+        }
+        Ok(p)
+    }
+
+    fn try_delegate<P: 'static, S: 'static, O>(
+        &mut self,
+        proxy: &P,
+        project: fn(&P) -> &S,
+        f: impl FnOnce(&S, &mut Self) -> Result<O, WiringError>,
+    ) -> Result<O, WiringError> {
+        self.path.push(Layer {
+            project: Arc::new(Projection(project)),
+            overrides: Vec::new(),
+        });
+        let result = f(project(proxy), self);
+        self.path.pop();
+        result
+    }
+
+    fn override_with<P: 'static, T: ResolvedBy<P> + 'static>(&mut self) {
+        if let Some(layer) = self.path.last_mut() {
+            layer
+                .overrides
+                .push((type_key::<T>(), resolve_override::<P, T>));
         }
-        self.get_provider().unwrap()
+    }
+
+    fn lazy_with<R: 'static, T: ResolvedBy<R> + 'static>(&mut self, _resolver: &R) -> Lazy<T> {
+        // The target type is not resolved yet, but the lazy parameter still depends on it: a child
+        // injector overriding the target type must not inherit the providers holding this parameter
+        let key = type_key::<T>();
+        if let Some(deps) = self.deps.last_mut().filter(|deps| !deps.contains(&key)) {
+            deps.push(key);
+        }
+        // Weak references avoid a cycle between the type map and the providers of lazy parameters
+        let tm = Arc::downgrade(&self.tm);
+        let root = Arc::downgrade(&self.root);
+        let config = Arc::downgrade(&self.config);
+        let path = self.path.clone();
+        Lazy::new(Box::new(move || {
+            let (Some(tm), Some(root), Some(config)) =
+                (tm.upgrade(), root.upgrade(), config.upgrade())
+            else {
+                return Err(WiringError::InjectorDropped {
+                    type_name: type_name::<T>(),
+                });
+            };
+            let mut resolution =
+                Resolution::new(tm, root.clone(), None, path.clone(), config.clone());
+            let mismatch = || WiringError::TypeMismatch {
+                type_name: type_name::<R>(),
+            };
+            let resolver = project(root.as_ref(), &path)
+                .and_then(|resolver| resolver.downcast_ref::<R>())
+                .ok_or_else(mismatch)?;
+            resolution
+                .try_resolve_with::<R, T>(resolver)
+                .map_err(|e| config.check_error(e))
+        }))
     }
 }
 
@@ -160,16 +733,16 @@ macro_rules! callable_tuple ({ $($param:ident)* } => {
         fn inject(
             _resolver: &R,
             _injector: &mut impl ProviderMap,
-        ) -> Self {
-            ($(_injector.resolve_with::<R,$param>(_resolver).provide(),)*)
+        ) -> Result<Self, WiringError> {
+            Ok(($(_injector.try_resolve_with::<R,$param>(_resolver)?.try_provide()?,)*))
         }
 
         #[inline]
         fn provide(
             _resolver: &R,
             _injector: &mut impl ProviderMap,
-        ) -> Provider<Self> {
-            Arc::new(($(_injector.resolve_with::<R,$param>(_resolver).clone(),)*))
+        ) -> Result<Provider<Self>, WiringError> {
+            Ok(Arc::new(($(_injector.try_resolve_with::<R,$param>(_resolver)?.clone(),)*)))
         }
 }
 
@@ -181,6 +754,11 @@ macro_rules! callable_tuple ({ $($param:ident)* } => {
             let ($($param,)*) = self;
             ($($param.provide(),)*)
         }
+
+        fn try_provide(&self) -> Result<($($param,)*), WiringError> {
+            let ($($param,)*) = self;
+            Ok(($($param.try_provide()?,)*))
+        }
     }
 });
 
@@ -195,3 +773,47 @@ callable_tuple! { A B C D E F G }
 callable_tuple! { A B C D E F G H }
 callable_tuple! { A B C D E F G H I }
 callable_tuple! { A B C D E F G H I J }
+
+/// An AssistedCallable has a ```call_assisted``` function whose parameters are split in two tuples:
+/// the leading arguments supplied by the caller, followed by the injected dependencies.
+///
+/// This trait is implemented for all functions with up to 10 arguments, for each way to split them.
+pub trait AssistedCallable<Args, Deps, Ret> {
+    fn call_assisted(&self, args: Args, deps: Deps) -> Ret;
+}
+
+macro_rules! assisted_callable ({ [$($arg:ident)*] [$($dep:ident)*] } => {
+    impl<Func, Ret, $($arg,)* $($dep,)*> AssistedCallable<($($arg,)*), ($($dep,)*), Ret> for Func
+    where
+        Func: Fn($($arg,)* $($dep,)*) -> Ret,
+    {
+        #[inline]
+        #[allow(non_snake_case)]
+        fn call_assisted(&self, ($($arg,)*): ($($arg,)*), ($($dep,)*): ($($dep,)*)) -> Ret {
+            (self)($($arg,)* $($dep,)*)
+        }
+    }
+});
+
+// Move the parameters one by one from the dependencies to the arguments
+macro_rules! assisted_splits {
+    ([$($arg:ident)*], []) => {
+        assisted_callable! { [$($arg)*] [] }
+    };
+    ([$($arg:ident)*], [$first:ident $($dep:ident)*]) => {
+        assisted_callable! { [$($arg)*] [$first $($dep)*] }
+        assisted_splits!([$($arg)* $first], [$($dep)*]);
+    };
+}
+
+assisted_splits!([], []);
+assisted_splits!([], [A]);
+assisted_splits!([], [A B]);
+assisted_splits!([], [A B C]);
+assisted_splits!([], [A B C D]);
+assisted_splits!([], [A B C D E]);
+assisted_splits!([], [A B C D E F]);
+assisted_splits!([], [A B C D E F G]);
+assisted_splits!([], [A B C D E F G H]);
+assisted_splits!([], [A B C D E F G H I]);
+assisted_splits!([], [A B C D E F G H I J]);
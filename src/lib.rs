@@ -4,9 +4,14 @@
 //! The resolution rules are declared using macros that define (for a given resolution module) a map associating
 //! resolvable types to the constructors used to create instances. All parameters of the constructor must be
 //! resolvable types. Note that constructors are currently limited to 10 parameters.
+//! The resolver module itself must be ```Send + Sync + 'static```, as it is shared with the lazy parameters.
 //! These macros can be used multiple times on the same resolver module for different target types.
 //! Resolution rules can also be composed using submodules. The parent module must contain instances of the submodules
 //! and delegates the resolution of some of its associated types to the relevant submodule.
+//! The [resolve_delegated] macro either delegates all types resolved by a single submodule, or lists the types
+//! delegated to each submodule to compose several of them side by side.
+//! As all modules share the providers of the injector, a type resolved by the rules of two submodules is reported
+//! as a conflict: the parent then selects a single rule using an override layer.
 //!
 //!
 //! ```
@@ -43,23 +48,93 @@
 //! injector.inject_and_call(&some_injectable_function)
 //! ```
 //!
+//! # Factories
+//!
+//! Constructors can request a [resolve::Provider] of any resolved type instead of an instance: it acts as a factory
+//! which creates a fresh on-demand instance on each call to ```provide()```, without passing the whole injector around.
+//!
+//! For assisted injection, the [resolve_factory] macro binds a [resolve::Factory] whose ```create()``` function takes the
+//! leading parameters of the constructor as runtime arguments (e.g. a request payload) and injects the remaining ones.
+//!
+//! # Multibindings
+//!
+//! Several elements can be contributed to a collection of trait objects (```Vec<Arc<dyn Trait>>```) using the
+//! [resolve_multi] macro. The collection of a resolver module can include the contributions of its submodules.
+//! Similarly, the [resolve_map] macro contributes keyed entries to a ```HashMap<K, Arc<dyn Trait>>```,
+//! for example to build a dispatch table. Duplicate keys are reported as errors.
+//!
+//! # Qualified bindings
+//!
+//! The injector is keyed by type: several instances of the same type can be bound using a qualifier tag
+//! (```dyn Trait as Tag => constructor```). The tagged instance is injected as a [resolve::Named] wrapper
+//! (e.g. ```Named<Primary, Arc<dyn Database>>```) which dereferences to the inner value.
+//!
+//! # Scopes
+//!
+//! Bindings are either singletons ([resolve_singleton]) or on-demand instances ([resolve_instance]).
+//! The [resolve_scoped] macro declares a third lifetime: a scope created with [Hanami::scope] shares the singletons
+//! of its injector, but creates its own instance of scoped bindings which is dropped with the scope.
+//! For example, a single transaction can be shared by all components handling a request. The provider of a scoped
+//! binding can only be overridden within a scope, see [Hanami::set_provider].
+//!
+//! Singletons are constructed on their first injection. Singletons which must exist even if no component depends
+//! on them, e.g. a background scheduler, are declared with [HanamiBuilder::eager]: they are constructed when the
+//! injector is built.
+//!
+//! Similarly, the [resolve_thread_local] macro provides a separate instance to each thread (as a ```Rc<T>```):
+//! it is created on first use in this thread and dropped when the thread exits or with the injector.
+//!
 //! # Override
 //!
 //! The user can override the provider for a given target type **before the first runtime-resolution of this type**.
 //! This allows for example to set a mock or an alternative implementation at runtime.
-//! See the [Hanami::set_provider] function.
+//! See the [Hanami::set_provider] function. To override providers after resolution, for example in tests, the
+//! [Hanami::child] function creates a child injector which inherits the providers already resolved by its parent:
+//! only the providers depending on the overrides of the child are resolved again.
+//! [Hanami::replace_provider] instead replaces a provider of the injector itself, e.g. to reload a configuration:
+//! the providers depending on it are discarded and built again on their next injection.
+//!
+//! Alternatively, a [HanamiBuilder] configures the injector before its first use: it sets the overridden providers,
+//! the eager singletons, the listeners notified of each resolution, and options such as a strict mode which forbids
+//! any later override. The built injector can then be shared without mutable access.
+//!
+//! At compile time, a parent module can also replace some bindings of a submodule using an override layer:
+//! the overriding rules of the parent then apply to all types delegated to this submodule, see [resolve_delegated].
 //!
 //! # Panic on cyclical dependencies
 //!
 //! Cyclical dependencies between injected types avoid infinite loops but trigger a panic at runtime.
 //! As resolution rules are independent, they can not be detected at compile time (this is the case in
 //! [shaku](https://crates.io/crates/shaku) when using a single macro to define all resolution rules at once).
+//! The fallible variants [Hanami::try_inject] and [Hanami::try_inject_and_call] return a [resolve::WiringError]
+//! instead, which allows to report wiring failures without aborting.
+//! In both cases, the error reports the chain of types leading to the cycle, e.g. `A -> B -> A`.
+//! To detect these errors on startup rather than during the first request, the [roots] macro declares the entry
+//! points of the application: [Hanami::validate] then resolves all of them and returns all the wiring errors at once.
+//!
+//! Similarly, a panicking constructor is reported as a [resolve::WiringError::ConstructorPanicked] error.
+//! The failed resolution is rolled back: the injector remains usable and a later injection can retry.
+//!
+//! A [Lazy] parameter defers the resolution of its target type until its first use: it can break a cycle between
+//! singletons, and avoids building expensive components which are not always used.
+//!
+//! # Dependency graph
+//!
+//! The injector records the dependencies of each resolved type: [Hanami::graph] returns the resolved types with
+//! their lifetime and direct dependencies, in creation order, for example to inspect the wiring in tests.
+//! It can also be rendered as a diagram using [Hanami::export_dot] (Graphviz) or [Hanami::export_mermaid].
+//! For tooling, [Hanami::export_json] reports the origin, construction duration and number of provided instances
+//! of each resolved type, for example to compare the wiring of two releases.
 //!
 
+mod builder;
+pub mod graph;
 mod inject;
 pub mod resolve;
+mod typemap;
 
-pub use inject::Hanami;
+pub use builder::{CycleBehaviour, HanamiBuilder, ResolutionEvent};
+pub use inject::{Hanami, Lazy};
 
 #[cfg(test)]
 mod tests;
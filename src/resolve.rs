@@ -17,10 +17,19 @@
 //! * The [ProviderMap] trait describes a collection of providers (in practice using a type map).
 //!   It is the base trait for the dependency injection but has no compile time guarantees.
 
-use std::sync::Arc;
+use std::any::{type_name, Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
 use thiserror::Error;
 
-use crate::inject::Callable;
+use crate::inject::{AssistedCallable, Callable, Lazy};
 
 /// Provide an instance of a given type
 ///
@@ -29,11 +38,36 @@ use crate::inject::Callable;
 /// and on-demand instances (the provider is a factory).
 pub trait Provide<T>: Send + Sync {
     fn provide(&self) -> T;
+
+    /// Provide an instance, or return an error if it could not be created.
+    ///
+    /// The default implementation relies on [Provide::provide] and never fails.
+    fn try_provide(&self) -> Result<T, WiringError> {
+        Ok(self.provide())
+    }
 }
 
 /// Shared trait object implementing [Provide]
 pub type Provider<T> = Arc<dyn Provide<T>>;
 
+/// Create instances of a given type from runtime arguments
+///
+/// This is the counterpart of [Provide] for assisted injection: the arguments are supplied
+/// by the caller, while the other dependencies of the instance are injected.
+pub trait Create<Args, T>: Send + Sync {
+    fn create(&self, args: Args) -> T;
+
+    /// Create an instance, or return an error if it could not be created.
+    ///
+    /// The default implementation relies on [Create::create] and never fails.
+    fn try_create(&self, args: Args) -> Result<T, WiringError> {
+        Ok(self.create(args))
+    }
+}
+
+/// Shared trait object implementing [Create]
+pub type Factory<Args, T> = Arc<dyn Create<Args, T>>;
+
 /// Generic collection of providers
 ///
 /// This trait represents a map associating a type to a provider for this type.
@@ -43,61 +77,256 @@ pub trait ProviderMap: Sized {
     ///
     /// If the provider is already stored in the map, returns the existing provider,
     ///  otherwise use the resolver module to build a new provider and store it in the map.
-    fn resolve_with<R, T: ResolvedBy<R> + 'static>(&mut self, resolver: &R) -> &Provider<T>;
+    ///
+    /// Return an error if the provider could not be built
+    fn try_resolve_with<R, T: ResolvedBy<R> + 'static>(
+        &mut self,
+        resolver: &R,
+    ) -> Result<Provider<T>, WiringError>;
+
+    /// Obtain a provider for the target type.
+    ///
+    /// Panic if the provider could not be built, see [ProviderMap::try_resolve_with]
+    fn resolve_with<R, T: ResolvedBy<R> + 'static>(&mut self, resolver: &R) -> Provider<T> {
+        match self.try_resolve_with(resolver) {
+            Ok(p) => p,
+            Err(e) => panic!("{}", e),
+        }
+    }
 
     /// Call a function after injecting its parameter(s).
+    ///
+    /// Panic if a parameter could not be resolved, see [ProviderMap::try_inject_and_call]
     fn inject_and_call<R, F, I, O>(&mut self, resolver: &R, f: F) -> O
     where
         I: Injectable<R>,
         F: Callable<I, O>,
     {
-        f.call(I::inject(resolver, self))
+        match self.try_inject_and_call(resolver, f) {
+            Ok(o) => o,
+            Err(e) => panic!("{}", e),
+        }
     }
 
+    /// Call a function after injecting its parameter(s).
+    ///
+    /// Return an error if a parameter could not be resolved
+    fn try_inject_and_call<R, F, I, O>(&mut self, resolver: &R, f: F) -> Result<O, WiringError>
+    where
+        I: Injectable<R>,
+        F: Callable<I, O>,
+    {
+        Ok(f.call(I::inject(resolver, self)?))
+    }
+
+    /// Resolve the target type with the rule of the parent module whenever the current submodule resolves it.
+    ///
+    /// This is used by a parent module to replace a binding of a submodule during a resolution step delegated
+    /// to this submodule, including in the [Lazy] parameters created by this step, see [crate::resolve_delegated].
+    fn override_with<P: 'static, T: ResolvedBy<P> + 'static>(&mut self);
+
+    /// Run a resolution step with a submodule of the current resolver module.
+    ///
+    /// The path to the submodule is recorded to resolve [Lazy] parameters later, see [crate::resolve_delegated]
+    fn try_delegate<P: 'static, S: 'static, O>(
+        &mut self,
+        proxy: &P,
+        project: fn(&P) -> &S,
+        f: impl FnOnce(&S, &mut Self) -> Result<O, WiringError>,
+    ) -> Result<O, WiringError>;
+
+    /// Create a lazy parameter which will resolve the target type with the given resolver on first use
+    fn lazy_with<R: 'static, T: ResolvedBy<R> + 'static>(&mut self, resolver: &R) -> Lazy<T>;
+
     /// Obtain a provider for the parameter(s) of a callable function
-    fn inject_provider<R, F, I, O>(&mut self, _resolver: &R, _f: F) -> Provider<I>
+    fn inject_provider<R, F, I, O>(
+        &mut self,
+        _resolver: &R,
+        _f: F,
+    ) -> Result<Provider<I>, WiringError>
     where
         I: Injectable<R>,
         F: Callable<I, O>,
     {
         I::provide(_resolver, self)
     }
+
+    /// Obtain a provider for the injected parameter(s) of a callable function, following the leading arguments
+    fn inject_assisted_provider<R, F, A, I, O>(
+        &mut self,
+        _resolver: &R,
+        _f: F,
+    ) -> Result<Provider<I>, WiringError>
+    where
+        I: Injectable<R>,
+        F: AssistedCallable<A, I, O>,
+    {
+        I::provide(_resolver, self)
+    }
+}
+
+/// Lifetime of the instances provided by a resolution rule
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lifetime {
+    /// A single instance shared by the injector and all its scopes
+    Singleton,
+    /// A new instance is created for each injection
+    Transient,
+    /// A single instance shared within each scope, see [crate::Hanami::scope]
+    Scoped,
+    /// A single instance for each thread, see [crate::resolve_thread_local]
+    ThreadLocal,
 }
 
 /// Obtain a provider for the target type.
 pub trait Resolve<T>: Sized {
+    /// Lifetime of the provided instances.
+    ///
+    /// Transient providers can be built again in each scope, they are the safe default.
+    const LIFETIME: Lifetime = Lifetime::Transient;
+
     /// Construct a provider for the target type.
     ///
     /// This function should not be called directly but will be triggered by the injector when needed
-    fn build_provider(&self, injector: &mut impl ProviderMap) -> Provider<T>;
+    fn build_provider(&self, injector: &mut impl ProviderMap) -> Result<Provider<T>, WiringError>;
+
+    /// Resolver module whose rule builds the provider, used to detect conflicting rules.
+    ///
+    /// All modules of an injector share the same providers: two modules resolving the same type with their own rules
+    /// are reported as a [WiringError::ConflictingRules]. Delegated rules report the module of their submodule.
+    /// Return [None] if the module is unknown, the rule is then never reported as conflicting.
+    fn module() -> Option<TypeId> {
+        None
+    }
 }
 
 /// Mark a type as resolvable by a given resolver
 pub trait ResolvedBy<R> {
-    fn build_provider(resolver: &R, injector: &mut impl ProviderMap) -> Provider<Self>;
+    const LIFETIME: Lifetime;
+
+    fn build_provider(
+        resolver: &R,
+        injector: &mut impl ProviderMap,
+    ) -> Result<Provider<Self>, WiringError>;
+
+    fn module() -> Option<TypeId>;
 }
 
 impl<T, R: Resolve<T>> ResolvedBy<R> for T {
-    fn build_provider(resolver: &R, injector: &mut impl ProviderMap) -> Provider<Self> {
+    const LIFETIME: Lifetime = <R as Resolve<T>>::LIFETIME;
+
+    fn build_provider(
+        resolver: &R,
+        injector: &mut impl ProviderMap,
+    ) -> Result<Provider<Self>, WiringError> {
         resolver.build_provider(injector)
     }
+
+    fn module() -> Option<TypeId> {
+        <R as Resolve<T>>::module()
+    }
 }
 
 /// Errors triggered during the autowiring process
+///
+/// Type names are obtained with [std::any::type_name] and are only meant for diagnostics.
 #[derive(Error, Debug)]
 pub enum WiringError {
-    #[error("Cyclic dependencies: trying to start resolving in an open slot")]
-    CyclicResolution,
-    #[error("Consistency error: trying to replace an existing dependency")]
-    AlreadyResolved,
+    #[error("Cyclic dependencies: {}", .path.join(" -> "))]
+    CyclicResolution { path: Vec<&'static str> },
+    #[error("Consistency error: trying to replace the existing provider of {type_name}")]
+    AlreadyResolved { type_name: &'static str },
+    #[error(
+        "The provider of {type_name} can not be replaced while the current thread is resolving it"
+    )]
+    ReplacedWhileResolving { type_name: &'static str },
+    #[error("Constructor panicked while resolving {type_name}: {message}")]
+    ConstructorPanicked {
+        type_name: &'static str,
+        message: String,
+    },
+    #[error("Consistency error: the stored provider of {type_name} has an unexpected type")]
+    TypeMismatch { type_name: &'static str },
+    #[error("Duplicate key {key} in the keyed collection {type_name}")]
+    DuplicateKey {
+        type_name: &'static str,
+        key: String,
+    },
+    #[error("Conflicting rules: {type_name} is resolved by the rules of several modules")]
+    ConflictingRules { type_name: &'static str },
+    #[error("Scoped binding {type_name} resolved outside of a scope")]
+    OutOfScope { type_name: &'static str },
+    #[error("Scoped binding {type_name} can only be overridden within a scope")]
+    ScopedOverride { type_name: &'static str },
+    #[error("Shared binding {type_name} can not depend on scoped bindings")]
+    ScopedDependency { type_name: &'static str },
+    #[error("The injector was dropped before resolving the lazy {type_name}")]
+    InjectorDropped { type_name: &'static str },
+    #[error("Duplicate override: the provider of {type_name} is set more than once")]
+    DuplicateOverride { type_name: &'static str },
+    #[error("The provider of {type_name} can not be overridden in strict mode")]
+    StrictMode { type_name: &'static str },
+    #[error("Constructor failed while resolving {type_name}: {source}")]
+    Constructor {
+        type_name: &'static str,
+        source: Box<dyn StdError + Send + Sync>,
+    },
+}
+
+impl WiringError {
+    /// Wrap the error returned by a fallible constructor of the target type
+    pub fn constructor<T>(source: impl Into<Box<dyn StdError + Send + Sync>>) -> Self {
+        Self::Constructor {
+            type_name: type_name::<T>(),
+            source: source.into(),
+        }
+    }
+
+    /// Report a key contributed more than once to a keyed collection of the target type
+    pub fn duplicate_key<T>(key: &impl Debug) -> Self {
+        Self::DuplicateKey {
+            type_name: type_name::<T>(),
+            key: format!("{:?}", key),
+        }
+    }
 }
 
 /// Mark a derived type as resolvable by a given resolver
 ///
 /// This trait is implemented for tuples of resolved types
 pub trait Injectable<R>: Sized {
-    fn inject(resolver: &R, injector: &mut impl ProviderMap) -> Self;
-    fn provide(resolver: &R, injector: &mut impl ProviderMap) -> Provider<Self>;
+    fn inject(resolver: &R, injector: &mut impl ProviderMap) -> Result<Self, WiringError>;
+    fn provide(
+        resolver: &R,
+        injector: &mut impl ProviderMap,
+    ) -> Result<Provider<Self>, WiringError>;
+}
+
+/// Contribute elements to a collection binding
+///
+/// A resolver module contributes its own elements to the collection, as well as the elements
+/// contributed by some of its submodules. This trait is implemented by the [crate::resolve_multi]
+/// and [crate::resolve_map] macros.
+pub trait Contribute<C>: Sized {
+    fn contribute(
+        &self,
+        injector: &mut impl ProviderMap,
+        target: &mut C,
+    ) -> Result<(), WiringError>;
+}
+
+/// Declare the root types of an application
+///
+/// The root types are the entry points of the application, for example its commands or request handlers:
+/// [crate::Hanami::warm_up] and [crate::Hanami::validate] resolve all of them at startup.
+/// This trait is implemented by the [crate::roots] macro.
+pub trait Roots: Sized {
+    fn visit_roots(visitor: &mut impl VisitRoot<Self>);
+}
+
+/// Visit each root type declared by a resolver module, see [Roots]
+pub trait VisitRoot<R> {
+    fn visit<T: ResolvedBy<R> + 'static>(&mut self);
 }
 
 /// Generic clone-based provider
@@ -134,22 +363,329 @@ impl<I, T, F: Callable<I, T> + Send + Sync> Provide<T> for InstanceProvider<I, F
     fn provide(&self) -> T {
         self.constructor.call(self.provider.provide())
     }
+
+    fn try_provide(&self) -> Result<T, WiringError> {
+        Ok(self.constructor.call(self.provider.try_provide()?))
+    }
+}
+
+impl<A, I, T, F: AssistedCallable<A, I, T> + Send + Sync> Create<A, T> for InstanceProvider<I, F> {
+    fn create(&self, args: A) -> T {
+        self.constructor
+            .call_assisted(args, self.provider.provide())
+    }
+
+    fn try_create(&self, args: A) -> Result<T, WiringError> {
+        Ok(self
+            .constructor
+            .call_assisted(args, self.provider.try_provide()?))
+    }
+}
+
+/// Generic provider for single-use instances based on a fallible callable constructor
+///
+/// The error of the constructor is reported as a [WiringError::Constructor] by [Provide::try_provide],
+/// [Provide::provide] panics instead.
+pub struct TryInstanceProvider<I, F, E> {
+    pub provider: Provider<I>,
+    pub constructor: F,
+    error: PhantomData<fn() -> E>,
+}
+
+impl<I, F, E> TryInstanceProvider<I, F, E> {
+    pub fn new(provider: Provider<I>, constructor: F) -> Self {
+        Self {
+            provider,
+            constructor,
+            error: PhantomData,
+        }
+    }
+}
+
+impl<I, T, E, F> Provide<T> for TryInstanceProvider<I, F, E>
+where
+    F: Callable<I, Result<T, E>> + Send + Sync,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn provide(&self) -> T {
+        match self.try_provide() {
+            Ok(t) => t,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn try_provide(&self) -> Result<T, WiringError> {
+        self.constructor
+            .call(self.provider.try_provide()?)
+            .map_err(WiringError::constructor::<T>)
+    }
+}
+
+impl<A, I, T, E, F> Create<A, T> for TryInstanceProvider<I, F, E>
+where
+    F: AssistedCallable<A, I, Result<T, E>> + Send + Sync,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn create(&self, args: A) -> T {
+        match self.try_create(args) {
+            Ok(t) => t,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn try_create(&self, args: A) -> Result<T, WiringError> {
+        self.constructor
+            .call_assisted(args, self.provider.try_provide()?)
+            .map_err(WiringError::constructor::<T>)
+    }
+}
+
+/// Qualified instance of a type
+///
+/// The [ProviderMap] is keyed by type: a qualifier wrapper enables to bind several instances of
+/// the same type in a single resolver (e.g. a primary and a replica database). The tag is a marker
+/// type which is never instantiated. A constructor selects an instance by requesting the matching
+/// ```Named<$Tag, $Type>``` parameter, which dereferences to the inner value.
+pub struct Named<Tag, T> {
+    value: T,
+    tag: PhantomData<fn() -> Tag>,
+}
+
+impl<Tag, T> Named<Tag, T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            tag: PhantomData,
+        }
+    }
+
+    /// Extract the inner value
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<Tag, T: Clone> Clone for Named<Tag, T> {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone())
+    }
+}
+
+impl<Tag, T> Deref for Named<Tag, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<Tag, T: Debug> Debug for Named<Tag, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple(&format!("Named<{}>", type_name::<Tag>()))
+            .field(&self.value)
+            .finish()
+    }
+}
+
+/// Wrap the instances of another provider with a qualifier tag
+pub struct NamedProvider<P>(P);
+
+impl<P> NamedProvider<P> {
+    pub fn new(provider: P) -> Self {
+        Self(provider)
+    }
+}
+
+impl<Tag, T, P: Provide<T>> Provide<Named<Tag, T>> for NamedProvider<P> {
+    fn provide(&self) -> Named<Tag, T> {
+        Named::new(self.0.provide())
+    }
+
+    fn try_provide(&self) -> Result<Named<Tag, T>, WiringError> {
+        Ok(Named::new(self.0.try_provide()?))
+    }
+}
+
+/// Instance of a thread-local provider for the current thread
+struct ThreadLocal {
+    /// Liveness of the provider, its instances are dropped with it
+    provider: Weak<()>,
+    instance: Box<dyn Any>,
+}
+
+thread_local! {
+    /// Instances of all thread-local providers for the current thread
+    static THREAD_LOCALS: RefCell<HashMap<usize, ThreadLocal>> = RefCell::new(HashMap::new());
+}
+
+/// Identifier of the next thread-local provider
+static NEXT_THREAD_LOCAL: AtomicUsize = AtomicUsize::new(0);
+
+/// Generic provider for instances shared within each thread
+///
+/// The instance of a thread is created on first use by the wrapped provider and dropped when the thread exits,
+/// or when the provider is dropped. Instances are not [Send] nor [Sync], they are provided as a ```Rc<T>```:
+/// they can only be dropped by their own thread. When the provider is dropped, the instance of the current thread
+/// is dropped immediately, while the other threads drop their instance on their next use of a thread-local provider.
+pub struct ThreadLocalProvider<P> {
+    id: usize,
+    alive: Arc<()>,
+    factory: P,
+}
+
+impl<P> ThreadLocalProvider<P> {
+    pub fn new(factory: P) -> Self {
+        Self {
+            id: NEXT_THREAD_LOCAL.fetch_add(1, Ordering::Relaxed),
+            alive: Arc::new(()),
+            factory,
+        }
+    }
+
+    fn local<T: 'static>(
+        &self,
+        create: impl FnOnce() -> Result<T, WiringError>,
+    ) -> Result<Rc<T>, WiringError> {
+        // Instances are never dropped while the map is borrowed: their destructor may use other thread-local providers
+        let (cached, dropped) = THREAD_LOCALS.with(|locals| {
+            let mut locals = locals.borrow_mut();
+            let dead: Vec<_> = locals
+                .iter()
+                .filter(|(_, local)| local.provider.strong_count() == 0)
+                .map(|(id, _)| *id)
+                .collect();
+            let dropped: Vec<_> = dead.iter().filter_map(|id| locals.remove(id)).collect();
+            let cached = locals
+                .get(&self.id)
+                .and_then(|local| local.instance.downcast_ref::<Rc<T>>())
+                .cloned();
+            (cached, dropped)
+        });
+        drop(dropped);
+        if let Some(t) = cached {
+            return Ok(t);
+        }
+
+        // The constructor may use other thread-local providers: the map is not borrowed while creating the instance
+        let t = Rc::new(create()?);
+        let local = ThreadLocal {
+            provider: Arc::downgrade(&self.alive),
+            instance: Box::new(t.clone()),
+        };
+        let replaced = THREAD_LOCALS.with(|locals| locals.borrow_mut().insert(self.id, local));
+        drop(replaced);
+        Ok(t)
+    }
+}
+
+impl<P> Drop for ThreadLocalProvider<P> {
+    fn drop(&mut self) {
+        // The map is no longer available while the thread exits, its instances are then dropped with it
+        let dropped = THREAD_LOCALS
+            .try_with(|locals| {
+                locals
+                    .try_borrow_mut()
+                    .ok()
+                    .and_then(|mut locals| locals.remove(&self.id))
+            })
+            .ok()
+            .flatten();
+        drop(dropped);
+    }
+}
+
+impl<T: 'static, P: Provide<T>> Provide<Rc<T>> for ThreadLocalProvider<P> {
+    fn provide(&self) -> Rc<T> {
+        match self.try_provide() {
+            Ok(t) => t,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn try_provide(&self) -> Result<Rc<T>, WiringError> {
+        self.local(|| self.factory.try_provide())
+    }
 }
 
 /// Declare that a field of the parent type is a resolver submodules.
 ///
 /// This will import and delegate all resolution rules of the submodule using a blanket implementation.
 /// Note that conflicts can appear if a type is resolved by both the submodule and the parent (directly or through another submodule).
+///
+/// To compose several submodules, the delegated types of each submodule can be listed explicitly
+/// (```$Resolver => $field [$Type, ...]```): only these types are delegated, without blanket implementation.
+/// The parent can then combine any number of submodules with its own resolution rules, as long as each
+/// type is resolved only once. Dependencies of a delegated type are resolved by the submodule itself.
+/// Both forms can be mixed, but a parent can only use a single blanket delegation.
+///
+/// All modules share the providers of the injector, which are keyed by type only: if two submodules resolve
+/// the same internal dependency with their own rules, the second one is reported as a [WiringError::ConflictingRules]
+/// instead of silently using the provider built by the first one. The parent can then select a single rule for
+/// both submodules with an override layer.
+///
+/// # Override layers
+///
+/// With explicit delegation, the parent can also replace some bindings of a submodule
+/// (```$Resolver => $field [$Type, ...] override [$Override, ...]```). The precedence rules are:
+///
+/// * each overridden type must be resolved by the parent itself, otherwise the parent does not compile;
+/// * an overridden type can not be delegated, this would be a conflicting implementation;
+/// * the parent rule applies to all the dependencies of the delegated types, including the internal
+///   dependencies of the submodule and the [Lazy] parameters it creates.
+///
+/// The first two rules are checked at compile time. The last one is applied by the injector: whenever the
+/// submodule resolves an overridden type while building a delegated type, the rule of the parent is used instead,
+/// so the rule of the submodule is never used for this type, regardless of the resolution order.
+///
+/// Overrides are not applied to the collections contributed by submodules through [crate::resolve_multi]
+/// and [crate::resolve_map].
 #[macro_export]
 macro_rules! resolve_delegated {
-    ($Proxy: ty $(, $Resolver:ty => $field: ident)+ ) => {
+    (@delegate $Proxy:ty, $Resolver:ty, $field:ident, [$($Override:ty),* $(,)?], $Type:ty) => {
+        impl $crate::resolve::Resolve<$Type> for $Proxy {
+            const LIFETIME: $crate::resolve::Lifetime = <$Type as $crate::resolve::ResolvedBy<$Resolver>>::LIFETIME;
+
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<$Type>, $crate::resolve::WiringError> {
+                injector.try_delegate(self, |proxy: &$Proxy| &proxy.$field, |sub, injector| {
+                    $(
+                    injector.override_with::<$Proxy, $Override>();
+                    )*
+                    <$Type as $crate::resolve::ResolvedBy<$Resolver>>::build_provider(sub, injector)
+                })
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                <$Type as $crate::resolve::ResolvedBy<$Resolver>>::module()
+            }
+        }
+        $crate::resolve_derived!($Proxy, $Type);
+    };
+    ($Proxy:ty $(,)?) => {};
+    ($Proxy:ty, $Resolver:ty => $field:ident [$($Type:ty),+ $(,)?] override $overrides:tt $(, $($rest:tt)*)?) => {
         $(
+        $crate::resolve_delegated!(@delegate $Proxy, $Resolver, $field, $overrides, $Type);
+        )+
+        $crate::resolve_delegated!($Proxy $(, $($rest)*)?);
+    };
+    ($Proxy:ty, $Resolver:ty => $field:ident [$($Type:ty),+ $(,)?] $(, $($rest:tt)*)?) => {
+        $(
+        $crate::resolve_delegated!(@delegate $Proxy, $Resolver, $field, [], $Type);
+        )+
+        $crate::resolve_delegated!($Proxy $(, $($rest)*)?);
+    };
+    ($Proxy:ty, $Resolver:ty => $field:ident $(, $($rest:tt)*)?) => {
         impl<T: $crate::resolve::ResolvedBy<$Resolver>> $crate::resolve::Resolve<T> for $Proxy {
-            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> $crate::resolve::Provider<T> {
-                T::build_provider(&self.$field, injector)
+            const LIFETIME: $crate::resolve::Lifetime = T::LIFETIME;
+
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<T>, $crate::resolve::WiringError> {
+                injector.try_delegate(self, |proxy: &$Proxy| &proxy.$field, T::build_provider)
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                T::module()
             }
         }
-    )+
+        $crate::resolve_delegated!($Proxy $(, $($rest)*)?);
     };
 }
 
@@ -158,17 +694,143 @@ macro_rules! resolve_delegated {
 /// This macro provides a generic implementation of ```Resolve<Arc<$Type>>``` for ```$Resolver```.
 /// The singleton instance is obtained by calling the ```$constructor``` function.
 /// All parameters of this function must be injectable using the same resolver type.
+///
+/// Fallible constructors returning a ```Result``` are marked with the ```try``` keyword
+/// (```$Type => try $constructor```): their error is reported as a [WiringError::Constructor]
+/// by the fallible injection API.
+///
+/// Several singletons of the same type can be bound using a qualifier tag
+/// (```$Type as $Tag => $constructor```): this implements ```Resolve<Named<$Tag, Arc<$Type>>>```
+/// instead, see [Named].
 #[macro_export]
 macro_rules! resolve_singleton {
-    ($Resolver:ty $(, $Type:ty => $constructor: expr)+) => {
-        $(
+    (@construct $injector:ident, $self:ident, $Target:ty, [try], $constructor: expr) => {
+        $injector.try_inject_and_call($self, &$constructor)?
+            .map_err($crate::resolve::WiringError::constructor::<$Target>)?
+    };
+    (@construct $injector:ident, $self:ident, $Target:ty, [], $constructor: expr) => {
+        $injector.try_inject_and_call($self, &$constructor)?
+    };
+    (@named $Resolver:ty, $Type:ty, $Tag:ty, $Lifetime:ident, [$($try:tt)?], $constructor: expr) => {
+        impl $crate::resolve::Resolve<$crate::resolve::Named<$Tag, Arc<$Type>>> for $Resolver {
+            const LIFETIME: $crate::resolve::Lifetime = $crate::resolve::Lifetime::$Lifetime;
+
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<$crate::resolve::Named<$Tag, Arc<$Type>>>, $crate::resolve::WiringError> {
+                let singleton: Arc<$Type> = Arc::new(
+                    $crate::resolve_singleton!(@construct injector, self, $crate::resolve::Named<$Tag, Arc<$Type>>, [$($try)?], $constructor)
+                );
+                Ok($crate::resolve::SingletonProvider::build($crate::resolve::Named::new(singleton)))
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<Self>())
+            }
+        }
+        $crate::resolve_derived!($Resolver, $crate::resolve::Named<$Tag, Arc<$Type>>);
+    };
+    (@plain $Resolver:ty, $Type:ty, $Lifetime:ident, [$($try:tt)?], $constructor: expr) => {
         impl $crate::resolve::Resolve<Arc<$Type>> for $Resolver {
-            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> $crate::resolve::Provider<Arc<$Type>> {
-                let singleton: Arc<$Type> = Arc::new(injector.inject_and_call(self, &$constructor));
-                $crate::resolve::SingletonProvider::build(singleton)
+            const LIFETIME: $crate::resolve::Lifetime = $crate::resolve::Lifetime::$Lifetime;
+
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<Arc<$Type>>, $crate::resolve::WiringError> {
+                let singleton: Arc<$Type> = Arc::new(
+                    $crate::resolve_singleton!(@construct injector, self, Arc<$Type>, [$($try)?], $constructor)
+                );
+                Ok($crate::resolve::SingletonProvider::build(singleton))
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<Self>())
             }
         }
-        )+
+        $crate::resolve_derived!($Resolver, Arc<$Type>);
+    };
+    ($Resolver:ty $(,)?) => {};
+    ($Resolver:ty, $Type:ty as $Tag:ty => try $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_singleton!(@named $Resolver, $Type, $Tag, Singleton, [try], $constructor);
+        $crate::resolve_singleton!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $Type:ty as $Tag:ty => $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_singleton!(@named $Resolver, $Type, $Tag, Singleton, [], $constructor);
+        $crate::resolve_singleton!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $Type:ty => try $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_singleton!(@plain $Resolver, $Type, Singleton, [try], $constructor);
+        $crate::resolve_singleton!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $Type:ty => $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_singleton!(@plain $Resolver, $Type, Singleton, [], $constructor);
+        $crate::resolve_singleton!($Resolver $(, $($rest)*)?);
+    };
+}
+
+/// Declare that our resolver module can provide an instance of the selected type shared within each scope.
+///
+/// This macro provides a generic implementation of ```Resolve<Arc<$Type>>``` for ```$Resolver```,
+/// using the same syntax as [resolve_singleton] (including the ```try``` and ```$Type as $Tag``` forms).
+/// The instance is created once in each scope (see [crate::Hanami::scope]) and dropped with the scope,
+/// for example to share a transaction between all components handling a request.
+///
+/// Components depending on a scoped binding are also resolved in the scope: on-demand instances are then
+/// created with the instance of the current scope, while singletons can not depend on scoped bindings.
+/// Resolving a scoped binding outside of a scope is reported as a [WiringError::OutOfScope].
+#[macro_export]
+macro_rules! resolve_scoped {
+    ($Resolver:ty $(,)?) => {};
+    ($Resolver:ty, $Type:ty as $Tag:ty => try $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_singleton!(@named $Resolver, $Type, $Tag, Scoped, [try], $constructor);
+        $crate::resolve_scoped!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $Type:ty as $Tag:ty => $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_singleton!(@named $Resolver, $Type, $Tag, Scoped, [], $constructor);
+        $crate::resolve_scoped!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $Type:ty => try $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_singleton!(@plain $Resolver, $Type, Scoped, [try], $constructor);
+        $crate::resolve_scoped!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $Type:ty => $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_singleton!(@plain $Resolver, $Type, Scoped, [], $constructor);
+        $crate::resolve_scoped!($Resolver $(, $($rest)*)?);
+    };
+}
+
+/// Declare that our resolver module can provide an instance of the selected type for each thread.
+///
+/// This macro provides a generic implementation of ```Resolve<Rc<$Type>>``` for ```$Resolver```, where ```$Type```
+/// is a concrete type which does not need to be [Send] nor [Sync], for example a cache or a buffer.
+/// Each thread using the injector obtains its own instance: it is created by calling the ```$constructor``` function
+/// on first use in this thread and dropped when the thread exits or with the injector. All parameters of this function must be injectable
+/// using the same resolver type, for example shared singletons.
+///
+/// Fallible constructors returning a ```Result``` are marked with the ```try``` keyword (```$Type => try $constructor```):
+/// as with on-demand instances, their error is reported when providing an instance.
+#[macro_export]
+macro_rules! resolve_thread_local {
+    (@local $Resolver:ty, $Type:ty, $Factory:ident, $constructor: expr) => {
+        impl $crate::resolve::Resolve<std::rc::Rc<$Type>> for $Resolver {
+            const LIFETIME: $crate::resolve::Lifetime = $crate::resolve::Lifetime::ThreadLocal;
+
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<std::rc::Rc<$Type>>, $crate::resolve::WiringError> {
+                let prv = injector.inject_provider(self, $constructor)?;
+                let factory = $crate::resolve::$Factory::new(prv, $constructor);
+                Ok(Arc::new($crate::resolve::ThreadLocalProvider::new(factory)))
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<Self>())
+            }
+        }
+        $crate::resolve_derived!($Resolver, std::rc::Rc<$Type>);
+    };
+    ($Resolver:ty $(,)?) => {};
+    ($Resolver:ty, $Type:ty => try $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_thread_local!(@local $Resolver, $Type, TryInstanceProvider, $constructor);
+        $crate::resolve_thread_local!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $Type:ty => $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_thread_local!(@local $Resolver, $Type, InstanceProvider, $constructor);
+        $crate::resolve_thread_local!($Resolver $(, $($rest)*)?);
     };
 }
 
@@ -180,34 +842,291 @@ macro_rules! resolve_singleton {
 /// the concrete type to generate a wrapper between the concrete type and the target. This wrapper uses a
 /// struct named ```{$Resover}Wrapper``` that must be created beforehand as it must be local to be allowed
 ///  to add impl and we want to share a single generic struct as much as possible.
+///
+/// Fallible constructors returning a ```Result``` are marked with the ```try``` keyword
+/// (```$Type => try $constructor``` or ```$bx: $Type => $Concrete: try $constructor```).
+/// As instances are created on demand, their error is reported when providing an instance:
+/// [Provide::try_provide] returns a [WiringError::Constructor] while [Provide::provide] panics.
+///
+/// Raw types can also be bound with a qualifier tag (```$Type as $Tag => $constructor```):
+/// this implements ```Resolve<Named<$Tag, $Type>>``` instead, see [Named].
 #[macro_export]
 macro_rules! resolve_instance {
-    ($Resolver:ty $(, $Type:ty => $constructor: expr)+) => {
-        $(
+    (@wrapper $Resolver:ty, $bx: ident : $Type:ty => $Concrete: ty) => {
+        impl<T: $crate::resolve::Provide<$Concrete>> $crate::resolve::Provide<$bx<$Type>> for paste::paste! { [< $Resolver Wrapper >]<T> } {
+            fn provide(&self) -> $bx<$Type> {
+                let concrete: $Concrete = self.0.provide();
+                $bx::new(concrete)
+            }
+
+            fn try_provide(&self) -> Result<$bx<$Type>, $crate::resolve::WiringError> {
+                let concrete: $Concrete = self.0.try_provide()?;
+                Ok($bx::new(concrete))
+            }
+        }
+    };
+    (@boxed $Resolver:ty, $bx: ident : $Type:ty => $Concrete: ty, $Factory:ident, $constructor: expr) => {
+        $crate::resolve_instance!(@wrapper $Resolver, $bx : $Type => $Concrete);
+        impl $crate::resolve::Resolve<$bx<$Type>> for $Resolver {
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<$bx<$Type>>, $crate::resolve::WiringError> {
+                let prv = injector.inject_provider(self, $constructor)?;
+                let factory = $crate::resolve::$Factory::new(prv, $constructor);
+                Ok(Arc::new(paste::paste! { [< $Resolver Wrapper >] }(factory)))
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<Self>())
+            }
+        }
+        $crate::resolve_derived!($Resolver, $bx<$Type>);
+    };
+    (@named $Resolver:ty, $Type:ty, $Tag:ty, $Factory:ident, $constructor: expr) => {
+        impl $crate::resolve::Resolve<$crate::resolve::Named<$Tag, $Type>> for $Resolver {
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<$crate::resolve::Named<$Tag, $Type>>, $crate::resolve::WiringError> {
+                let prv = injector.inject_provider(self, $constructor)?;
+                let factory = $crate::resolve::$Factory::new(prv, $constructor);
+                Ok(Arc::new($crate::resolve::NamedProvider::new(factory)))
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<Self>())
+            }
+        }
+        $crate::resolve_derived!($Resolver, $crate::resolve::Named<$Tag, $Type>);
+    };
+    (@plain $Resolver:ty, $Type:ty, $Factory:ident, $constructor: expr) => {
         impl $crate::resolve::Resolve<$Type> for $Resolver {
-            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> $crate::resolve::Provider<$Type> {
-                let prv = injector.inject_provider(self, $constructor);
-                let factory = $crate::resolve::InstanceProvider::new(prv, $constructor);
-                Arc::new(factory)
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<$Type>, $crate::resolve::WiringError> {
+                let prv = injector.inject_provider(self, $constructor)?;
+                let factory = $crate::resolve::$Factory::new(prv, $constructor);
+                Ok(Arc::new(factory))
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<Self>())
             }
         }
+        $crate::resolve_derived!($Resolver, $Type);
+    };
+    ($Resolver:ty $(,)?) => {};
+    ($Resolver:ty, $bx: ident : $Type:ty => $Concrete: ty : try $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_instance!(@boxed $Resolver, $bx : $Type => $Concrete, TryInstanceProvider, $constructor);
+        $crate::resolve_instance!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $bx: ident : $Type:ty => $Concrete: ty : $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_instance!(@boxed $Resolver, $bx : $Type => $Concrete, InstanceProvider, $constructor);
+        $crate::resolve_instance!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $Type:ty as $Tag:ty => try $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_instance!(@named $Resolver, $Type, $Tag, TryInstanceProvider, $constructor);
+        $crate::resolve_instance!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $Type:ty as $Tag:ty => $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_instance!(@named $Resolver, $Type, $Tag, InstanceProvider, $constructor);
+        $crate::resolve_instance!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $Type:ty => try $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_instance!(@plain $Resolver, $Type, TryInstanceProvider, $constructor);
+        $crate::resolve_instance!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, $Type:ty => $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_instance!(@plain $Resolver, $Type, InstanceProvider, $constructor);
+        $crate::resolve_instance!($Resolver $(, $($rest)*)?);
+    };
+}
+
+/// Declare that our resolver module can create instances of the selected type from runtime arguments.
+///
+/// This macro provides a generic implementation of ```Resolve<Factory<($Arg, ...), $Type>>``` for ```$Resolver```
+/// (```($Arg, ...) -> $Type => $constructor```). The leading parameters of the constructor are the arguments of
+/// the factory: they are supplied on each call to [Create::create]. All its remaining parameters must be injectable
+/// using the same resolver type, they are resolved once when building the factory.
+///
+/// Fallible constructors returning a ```Result``` are marked with the ```try``` keyword
+/// (```($Arg, ...) -> $Type => try $constructor```): [Create::try_create] then returns a [WiringError::Constructor]
+/// while [Create::create] panics.
+#[macro_export]
+macro_rules! resolve_factory {
+    (@factory $Resolver:ty, [$($Arg:ty),*], $Type:ty, $Factory:ident, $constructor: expr) => {
+        impl $crate::resolve::Resolve<$crate::resolve::Factory<($($Arg,)*), $Type>> for $Resolver {
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<$crate::resolve::Factory<($($Arg,)*), $Type>>, $crate::resolve::WiringError> {
+                let prv = injector.inject_assisted_provider::<_, _, ($($Arg,)*), _, _>(self, $constructor)?;
+                let factory: $crate::resolve::Factory<($($Arg,)*), $Type> = Arc::new($crate::resolve::$Factory::new(prv, $constructor));
+                Ok($crate::resolve::SingletonProvider::build(factory))
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<Self>())
+            }
+        }
+        $crate::resolve_derived!($Resolver, $crate::resolve::Factory<($($Arg,)*), $Type>);
+    };
+    ($Resolver:ty $(,)?) => {};
+    ($Resolver:ty, ($($Arg:ty),* $(,)?) -> $Type:ty => try $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_factory!(@factory $Resolver, [$($Arg),*], $Type, TryInstanceProvider, $constructor);
+        $crate::resolve_factory!($Resolver $(, $($rest)*)?);
+    };
+    ($Resolver:ty, ($($Arg:ty),* $(,)?) -> $Type:ty => $constructor: expr $(, $($rest:tt)*)?) => {
+        $crate::resolve_factory!(@factory $Resolver, [$($Arg),*], $Type, InstanceProvider, $constructor);
+        $crate::resolve_factory!($Resolver $(, $($rest)*)?);
+    };
+}
+
+/// Declare that our resolver module contributes shared elements to a collection of trait objects.
+///
+/// This macro provides a generic implementation of ```Resolve<Vec<Arc<$Type>>>``` for ```$Resolver```.
+/// Each element is created once by calling one of the listed constructors: all parameters of these
+/// functions must be injectable using the same resolver type, and their return type must be coercible to
+/// ```Arc<$Type>```.
+///
+/// The collection can also include the elements contributed by submodules, listed after the constructors as
+/// fields of the resolver module (```$Type => [$constructor, ...] + [$field, ...]```).
+/// The order of the collection is deterministic: the elements of the resolver module come first in the
+/// order of the constructors, followed by the contributions of each submodule in the order of the fields.
+///
+/// A submodule which is wired using [resolve_delegated] also provides its collection to the parent module.
+/// Note that its blanket implementation conflicts with a collection of the same type declared by the parent.
+#[macro_export]
+macro_rules! resolve_multi {
+    ($Resolver:ty $(, $Type:ty => [$($constructor: expr),* $(,)?] $(+ [$($field: ident),+ $(,)?])?)+) => {
+        $(
+        impl $crate::resolve::Contribute<Vec<Arc<$Type>>> for $Resolver {
+            fn contribute(&self, injector: &mut impl $crate::resolve::ProviderMap, target: &mut Vec<Arc<$Type>>) -> Result<(), $crate::resolve::WiringError> {
+                $(
+                let element: Arc<$Type> = Arc::new(injector.try_inject_and_call(self, &$constructor)?);
+                target.push(element);
+                )*
+                $($(
+                injector.try_delegate(self, |proxy: &$Resolver| &proxy.$field, |sub, injector| {
+                    $crate::resolve::Contribute::<Vec<Arc<$Type>>>::contribute(sub, injector, target)
+                })?;
+                )+)?
+                Ok(())
+            }
+        }
+        impl $crate::resolve::Resolve<Vec<Arc<$Type>>> for $Resolver {
+            const LIFETIME: $crate::resolve::Lifetime = $crate::resolve::Lifetime::Singleton;
+
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<Vec<Arc<$Type>>>, $crate::resolve::WiringError> {
+                let mut elements = Vec::new();
+                $crate::resolve::Contribute::<Vec<Arc<$Type>>>::contribute(self, injector, &mut elements)?;
+                Ok($crate::resolve::SingletonProvider::build(elements))
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<Self>())
+            }
+        }
+        $crate::resolve_derived!($Resolver, Vec<Arc<$Type>>);
         )+
     };
-    ($Resolver:ty $(, $bx: ident : $Type:ty => $Concrete: ty : $constructor: expr)+) => {
+}
+
+/// Declare that our resolver module contributes shared entries to a keyed collection of trait objects.
+///
+/// This macro provides a generic implementation of ```Resolve<HashMap<$Key, Arc<$Type>>>``` for ```$Resolver```,
+/// for example to inject a dispatch table. Each entry associates a key to one of the listed constructors
+/// (```$Key => $Type => [$key => $constructor, ...]```). The element is created once by calling the constructor:
+/// all its parameters must be injectable using the same resolver type, and its return type must be coercible to
+/// ```Arc<$Type>```. The key type must implement ```Hash```, ```Eq``` and ```Debug```.
+///
+/// As with [resolve_multi], the collection can include the entries contributed by submodules, listed after
+/// the entries as fields of the resolver module (```$Key => $Type => [...] + [$field, ...]```).
+/// A key contributed more than once is reported as a [WiringError::DuplicateKey] instead of replacing
+/// the previous entry.
+///
+/// [WiringError::DuplicateKey]: crate::resolve::WiringError::DuplicateKey
+#[macro_export]
+macro_rules! resolve_map {
+    ($Resolver:ty $(, $Key:ty => $Type:ty => [$($key: expr => $constructor: expr),* $(,)?] $(+ [$($field: ident),+ $(,)?])?)+) => {
         $(
-        impl<T: $crate::resolve::Provide<$Concrete>> $crate::resolve::Provide<$bx<$Type>> for paste::paste! { [< $Resolver Wrapper >]<T> } {
-            fn provide(&self) -> $bx<$Type> {
-                let concrete: $Concrete = self.0.provide();
-                $bx::new(concrete)
+        impl $crate::resolve::Contribute<std::collections::HashMap<$Key, Arc<$Type>>> for $Resolver {
+            fn contribute(&self, injector: &mut impl $crate::resolve::ProviderMap, target: &mut std::collections::HashMap<$Key, Arc<$Type>>) -> Result<(), $crate::resolve::WiringError> {
+                $(
+                let key: $Key = $key;
+                if target.contains_key(&key) {
+                    return Err($crate::resolve::WiringError::duplicate_key::<std::collections::HashMap<$Key, Arc<$Type>>>(&key));
+                }
+                let element: Arc<$Type> = Arc::new(injector.try_inject_and_call(self, &$constructor)?);
+                target.insert(key, element);
+                )*
+                $($(
+                injector.try_delegate(self, |proxy: &$Resolver| &proxy.$field, |sub, injector| {
+                    $crate::resolve::Contribute::<std::collections::HashMap<$Key, Arc<$Type>>>::contribute(sub, injector, target)
+                })?;
+                )+)?
+                Ok(())
             }
         }
-        impl $crate::resolve::Resolve<$bx<$Type>> for $Resolver {
-            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> $crate::resolve::Provider<$bx<$Type>> {
-                let prv = injector.inject_provider(self, $constructor);
-                let factory = $crate::resolve::InstanceProvider::new(prv, $constructor);
-                Arc::new(paste::paste! { [< $Resolver Wrapper >] }(factory))
+        impl $crate::resolve::Resolve<std::collections::HashMap<$Key, Arc<$Type>>> for $Resolver {
+            const LIFETIME: $crate::resolve::Lifetime = $crate::resolve::Lifetime::Singleton;
+
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<std::collections::HashMap<$Key, Arc<$Type>>>, $crate::resolve::WiringError> {
+                let mut entries = std::collections::HashMap::new();
+                $crate::resolve::Contribute::<std::collections::HashMap<$Key, Arc<$Type>>>::contribute(self, injector, &mut entries)?;
+                Ok($crate::resolve::SingletonProvider::build(entries))
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<Self>())
+            }
+        }
+        $crate::resolve_derived!($Resolver, std::collections::HashMap<$Key, Arc<$Type>>);
+        )+
+    };
+}
+
+/// Declare that our resolver module can inject the derived parameters of the selected types.
+///
+/// This macro provides generic implementations of ```Resolve<Lazy<$Type>>``` and ```Resolve<Provider<$Type>>```
+/// for ```$Resolver```, where ```$Type``` must be resolved by the same resolver.
+/// A [Provider] parameter acts as a factory: each call to [Provide::provide] creates a fresh on-demand instance
+/// (or returns the shared singleton). A [Lazy] parameter only resolves the target type on first use.
+///
+/// This macro is already used by all resolution macros for the types that they resolve and is only needed with
+/// hand-written implementations of [Resolve].
+#[macro_export]
+macro_rules! resolve_derived {
+    ($Resolver:ty $(, $Type:ty)+ $(,)?) => {
+        $(
+        impl $crate::resolve::Resolve<$crate::Lazy<$Type>> for $Resolver {
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<$crate::Lazy<$Type>>, $crate::resolve::WiringError> {
+                Ok($crate::resolve::SingletonProvider::build(injector.lazy_with::<$Resolver, $Type>(self)))
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                <$Type as $crate::resolve::ResolvedBy<$Resolver>>::module()
+            }
+        }
+        impl $crate::resolve::Resolve<$crate::resolve::Provider<$Type>> for $Resolver {
+            fn build_provider(&self, injector: &mut impl $crate::resolve::ProviderMap) -> Result<$crate::resolve::Provider<$crate::resolve::Provider<$Type>>, $crate::resolve::WiringError> {
+                let provider = injector.try_resolve_with::<$Resolver, $Type>(self)?;
+                Ok($crate::resolve::SingletonProvider::build(provider))
+            }
+
+            fn module() -> Option<std::any::TypeId> {
+                <$Type as $crate::resolve::ResolvedBy<$Resolver>>::module()
             }
         }
         )+
     };
 }
+
+/// Declare the root types of our resolver module.
+///
+/// This macro provides an implementation of [Roots] for ```$Resolver```, listing the injected types of the
+/// entry points of the application (e.g. ```roots!(MyResolver, Arc<dyn Logger>, MyCommand)```).
+/// All root types must be resolved by the same resolver. The injector can then report the wiring errors
+/// of all root types at once on startup, see [crate::Hanami::validate].
+#[macro_export]
+macro_rules! roots {
+    ($Resolver:ty $(, $Type:ty)+ $(,)?) => {
+        impl $crate::resolve::Roots for $Resolver {
+            fn visit_roots(visitor: &mut impl $crate::resolve::VisitRoot<Self>) {
+                $(
+                visitor.visit::<$Type>();
+                )+
+            }
+        }
+    };
+}
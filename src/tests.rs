@@ -1,8 +1,21 @@
-use std::sync::Arc;
+use std::any::type_name;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Barrier, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use std::{fmt, io};
 
-use crate::{resolve, resolve_instance, resolve_singleton};
+use crate::{
+    resolve, resolve_delegated, resolve_factory, resolve_instance, resolve_map, resolve_multi,
+    resolve_scoped, resolve_singleton, resolve_thread_local, roots,
+};
 
-use super::Hanami;
+use super::{CycleBehaviour, Hanami, HanamiBuilder, Lazy, ResolutionEvent};
+use crate::resolve::{Factory, Named, Provider};
 
 trait TestTrait: Send + Sync {
     fn cheers(&self);
@@ -49,6 +62,477 @@ impl CyclicalB {
     }
 }
 
+/// Wait until two threads reached the same point, return false after a timeout
+fn rendezvous(counter: &(Mutex<usize>, Condvar)) -> bool {
+    let (count, cvar) = counter;
+    let mut count = count.lock().unwrap();
+    *count += 1;
+    cvar.notify_all();
+    let (_count, timeout) = cvar
+        .wait_timeout_while(count, Duration::from_secs(5), |c| *c < 2)
+        .unwrap();
+    !timeout.timed_out()
+}
+
+static PARALLEL: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+
+struct ParallelA(bool);
+impl ParallelA {
+    fn new() -> Self {
+        Self(rendezvous(&PARALLEL))
+    }
+}
+struct ParallelB(bool);
+impl ParallelB {
+    fn new() -> Self {
+        Self(rendezvous(&PARALLEL))
+    }
+}
+
+static RELOAD_STARTED: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+static RELOAD_REPLACED: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+
+struct Setting(&'static str);
+impl Setting {
+    fn initial() -> Self {
+        Self("initial")
+    }
+}
+/// Singleton whose construction overlaps the replacement of its dependency
+struct Reloadable {
+    setting: Arc<Setting>,
+}
+impl Reloadable {
+    fn new(setting: Arc<Setting>) -> Self {
+        rendezvous(&RELOAD_STARTED);
+        rendezvous(&RELOAD_REPLACED);
+        Self { setting }
+    }
+}
+
+static SCOPE_CYCLE: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+
+/// Scoped binding and singleton depending on each other, each built by a different thread
+struct ScopedCross;
+impl ScopedCross {
+    fn with(_: Arc<ScopedCrossHelper>, _: Arc<SharedCross>) -> Self {
+        Self
+    }
+}
+struct SharedCross;
+impl SharedCross {
+    fn with(_: Arc<SharedCrossHelper>, _: Arc<ScopedCross>) -> Self {
+        Self
+    }
+}
+struct ScopedCrossHelper;
+impl ScopedCrossHelper {
+    fn new() -> Self {
+        rendezvous(&SCOPE_CYCLE);
+        Self
+    }
+}
+struct SharedCrossHelper;
+impl SharedCrossHelper {
+    fn new() -> Self {
+        rendezvous(&SCOPE_CYCLE);
+        Self
+    }
+}
+
+static SLOW_BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+struct Slow;
+impl Slow {
+    fn new() -> Self {
+        SLOW_BUILDS.fetch_add(1, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+        Self
+    }
+}
+
+struct CrossA;
+impl CrossA {
+    fn with(_: Arc<CrossHelper>, _: Arc<CrossB>) -> Self {
+        Self
+    }
+}
+struct CrossB;
+impl CrossB {
+    fn with(_: Arc<SlowHelper>, _: Arc<CrossA>) -> Self {
+        Self
+    }
+}
+struct SlowHelper;
+impl SlowHelper {
+    fn new() -> Self {
+        thread::sleep(Duration::from_millis(50));
+        Self
+    }
+}
+// Distinct from Slow, whose builds are counted by concurrent_singleton_built_once
+struct CrossHelper;
+impl CrossHelper {
+    fn new() -> Self {
+        thread::sleep(Duration::from_millis(50));
+        Self
+    }
+}
+
+static FLAKY_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+struct Flaky;
+impl Flaky {
+    fn new() -> Self {
+        if FLAKY_ATTEMPTS.fetch_add(1, Ordering::SeqCst) == 0 {
+            panic!("first attempt fails");
+        }
+        Self
+    }
+}
+struct FlakyUser;
+impl FlakyUser {
+    fn new(_: Arc<Flaky>) -> Self {
+        Self
+    }
+}
+
+#[derive(Debug)]
+struct ConnectionRefused;
+impl fmt::Display for ConnectionRefused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "connection refused")
+    }
+}
+impl Error for ConnectionRefused {}
+
+struct Database;
+impl Database {
+    fn connect(_: Arc<dyn TestTrait>) -> Result<Self, ConnectionRefused> {
+        Err(ConnectionRefused)
+    }
+}
+struct Repository;
+impl Repository {
+    fn new(_: Arc<Database>) -> Self {
+        Self
+    }
+}
+struct Config;
+impl Config {
+    fn load() -> Result<Self, io::Error> {
+        Ok(Self)
+    }
+}
+struct Session;
+impl Session {
+    fn open(_: Arc<Config>) -> Result<Self, io::Error> {
+        Err(io::Error::other("session expired"))
+    }
+}
+
+trait Plugin: Send + Sync {
+    fn name(&self) -> &'static str;
+}
+
+macro_rules! test_plugin {
+    ($($Plugin:ident),+) => {
+        $(
+        struct $Plugin;
+        impl $Plugin {
+            fn new() -> Self {
+                Self
+            }
+        }
+        impl Plugin for $Plugin {
+            fn name(&self) -> &'static str {
+                stringify!($Plugin)
+            }
+        }
+        )+
+    };
+}
+test_plugin!(PluginA, PluginB, PluginC);
+
+struct HelperPlugin;
+impl HelperPlugin {
+    fn new(_: Arc<dyn TestTrait>) -> Self {
+        Self
+    }
+}
+impl Plugin for HelperPlugin {
+    fn name(&self) -> &'static str {
+        "HelperPlugin"
+    }
+}
+
+/// Qualifier tags
+struct Primary;
+struct Replica;
+
+struct Replication {
+    primary: Arc<dyn Plugin>,
+    replica: Arc<dyn Plugin>,
+}
+impl Replication {
+    fn new(
+        primary: Named<Primary, Arc<dyn Plugin>>,
+        replica: Named<Replica, Arc<dyn Plugin>>,
+    ) -> Self {
+        Self {
+            primary: primary.into_inner(),
+            replica: replica.into_inner(),
+        }
+    }
+}
+
+trait Logger: Send + Sync {
+    fn origin(&self) -> &'static str;
+}
+struct LibLogger;
+impl LibLogger {
+    fn new() -> Self {
+        Self
+    }
+}
+impl Logger for LibLogger {
+    fn origin(&self) -> &'static str {
+        "library"
+    }
+}
+struct AppLogger;
+impl AppLogger {
+    fn new(_: Arc<Formatter>) -> Self {
+        Self
+    }
+}
+impl Logger for AppLogger {
+    fn origin(&self) -> &'static str {
+        "application"
+    }
+}
+struct MockLogger;
+impl MockLogger {
+    fn new() -> Self {
+        Self
+    }
+}
+impl Logger for MockLogger {
+    fn origin(&self) -> &'static str {
+        "mock"
+    }
+}
+struct Formatter;
+impl Formatter {
+    fn new() -> Self {
+        Self
+    }
+}
+struct Audit {
+    logger: Arc<dyn Logger>,
+}
+impl Audit {
+    fn new(logger: Arc<dyn Logger>) -> Self {
+        Self { logger }
+    }
+}
+
+struct Storage {
+    logger: Arc<dyn Logger>,
+}
+impl Storage {
+    fn new(logger: Arc<dyn Logger>) -> Self {
+        Self { logger }
+    }
+}
+struct Http {
+    logger: Arc<dyn Logger>,
+}
+impl Http {
+    fn new(logger: Arc<dyn Logger>) -> Self {
+        Self { logger }
+    }
+}
+
+/// Library component resolving its logger on first use
+struct Tracer {
+    logger: Lazy<Arc<dyn Logger>>,
+}
+impl Tracer {
+    fn new(logger: Lazy<Arc<dyn Logger>>) -> Self {
+        Self { logger }
+    }
+}
+
+static JOURNAL_STARTED: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+static JOURNAL_RESUMED: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+
+/// Internal dependency of the library whose construction overlaps the replacement of a provider
+struct Clock;
+impl Clock {
+    fn new() -> Self {
+        rendezvous(&JOURNAL_STARTED);
+        rendezvous(&JOURNAL_RESUMED);
+        Self
+    }
+}
+struct Journal {
+    logger: Arc<dyn Logger>,
+}
+impl Journal {
+    fn new(_: Arc<Clock>, logger: Arc<dyn Logger>) -> Self {
+        Self { logger }
+    }
+}
+
+static EXPENSIVE_BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+struct Expensive;
+impl Expensive {
+    fn new() -> Self {
+        EXPENSIVE_BUILDS.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+struct Frugal {
+    expensive: Lazy<Arc<Expensive>>,
+}
+impl Frugal {
+    fn new(expensive: Lazy<Arc<Expensive>>) -> Self {
+        Self { expensive }
+    }
+}
+
+/// Singleton creating on-demand instances
+struct ActionRunner {
+    actions: Provider<SimpleAction>,
+}
+impl ActionRunner {
+    fn new(actions: Provider<SimpleAction>) -> Self {
+        Self { actions }
+    }
+    fn run(&self, times: usize) {
+        for _ in 0..times {
+            self.actions.provide().callme();
+        }
+    }
+}
+
+/// Request handler mixing runtime arguments with injected dependencies
+struct UserId(u32);
+struct Payload(&'static str);
+struct Handler {
+    user: u32,
+    payload: &'static str,
+    helper: Arc<dyn TestTrait>,
+}
+impl Handler {
+    fn new(user: UserId, payload: Payload, helper: Arc<dyn TestTrait>) -> Self {
+        Self {
+            user: user.0,
+            payload: payload.0,
+            helper,
+        }
+    }
+    fn anonymous(payload: Payload, helper: Arc<dyn TestTrait>) -> Result<Self, io::Error> {
+        if payload.0.is_empty() {
+            return Err(io::Error::other("empty payload"));
+        }
+        Ok(Self::new(UserId(0), payload, helper))
+    }
+}
+
+static TRANSACTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Scoped binding shared by the components of a unit of work
+struct Transaction(usize);
+impl Transaction {
+    fn begin() -> Self {
+        Self(TRANSACTIONS.fetch_add(1, Ordering::SeqCst))
+    }
+}
+struct UnitOfWork {
+    tx: Arc<Transaction>,
+}
+impl UnitOfWork {
+    fn new(tx: Arc<Transaction>) -> Self {
+        Self { tx }
+    }
+}
+struct TransactionLog;
+impl TransactionLog {
+    fn new(_: UnitOfWork) -> Self {
+        Self
+    }
+}
+
+static BUFFERS: AtomicUsize = AtomicUsize::new(0);
+static DROPPED_BUFFERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Thread-local component which is neither Send nor Sync
+struct Buffer {
+    id: usize,
+    data: RefCell<Vec<u8>>,
+    helper: Arc<dyn TestTrait>,
+}
+impl Buffer {
+    fn new(helper: Arc<dyn TestTrait>) -> Self {
+        Self {
+            id: BUFFERS.fetch_add(1, Ordering::SeqCst),
+            data: RefCell::new(Vec::new()),
+            helper,
+        }
+    }
+}
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        DROPPED_BUFFERS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+static DROPPED_CACHES: AtomicUsize = AtomicUsize::new(0);
+
+/// Thread-local component dropped with its injector
+struct Cache;
+impl Cache {
+    fn new() -> Self {
+        Self
+    }
+}
+impl Drop for Cache {
+    fn drop(&mut self) {
+        DROPPED_CACHES.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Singletons depending on each other, the cycle is broken by a lazy parameter
+static SCHEDULERS: AtomicUsize = AtomicUsize::new(0);
+
+struct Scheduler;
+impl Scheduler {
+    fn start(_: Arc<dyn TestTrait>) -> Self {
+        SCHEDULERS.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+struct Ping {
+    pong: Lazy<Arc<Pong>>,
+}
+impl Ping {
+    fn new(pong: Lazy<Arc<Pong>>) -> Self {
+        Self { pong }
+    }
+}
+struct Pong {
+    ping: Arc<Ping>,
+}
+impl Pong {
+    fn new(ping: Arc<Ping>) -> Self {
+        Self { ping }
+    }
+}
+
 struct SimpleAction;
 
 impl SimpleAction {
@@ -72,10 +556,171 @@ resolve_singleton!(TestModule,
     CyclicalB => CyclicalB::with
 );
 
+// define cyclical resolution rules with slow dependencies to trigger cross-thread cycles
+resolve_singleton!(TestModule,
+    CrossA => CrossA::with,
+    CrossB => CrossB::with,
+    SlowHelper => SlowHelper::new,
+    CrossHelper => CrossHelper::new,
+    SharedCross => SharedCross::with,
+    ScopedCrossHelper => ScopedCrossHelper::new,
+    SharedCrossHelper => SharedCrossHelper::new
+);
+
+resolve_singleton!(TestModule,
+    ParallelA => ParallelA::new,
+    ParallelB => ParallelB::new,
+    Slow => Slow::new
+);
+
+resolve_singleton!(TestModule,
+    Flaky => Flaky::new,
+    FlakyUser => FlakyUser::new
+);
+
+resolve_singleton!(TestModule,
+    Database => try Database::connect,
+    Repository => Repository::new,
+    Config => try Config::load
+);
+
 resolve_instance!(TestModule, SimpleAction => SimpleAction::create);
 
+resolve_scoped!(TestModule,
+    Transaction => Transaction::begin,
+    ScopedCross => ScopedCross::with
+);
+
+resolve_instance!(TestModule, UnitOfWork => UnitOfWork::new);
+
+resolve_thread_local!(TestModule, Buffer => Buffer::new, Cache => Cache::new);
+
+resolve_singleton!(TestModule, TransactionLog => TransactionLog::new);
+
+resolve_factory!(TestModule,
+    (UserId, Payload) -> Handler => Handler::new,
+    (Payload,) -> Handler => try Handler::anonymous
+);
+
+resolve_singleton!(TestModule,
+    Expensive => Expensive::new,
+    Frugal => Frugal::new,
+    Ping => Ping::new,
+    Pong => Pong::new,
+    ActionRunner => ActionRunner::new,
+    Setting => Setting::initial,
+    Reloadable => Reloadable::new,
+    Scheduler => Scheduler::start
+);
+
+resolve_singleton!(TestModule,
+    dyn Plugin as Primary => PluginA::new,
+    dyn Plugin as Replica => PluginB::new,
+    Replication => Replication::new
+);
+
+resolve_instance!(TestModule, SimpleAction as Primary => SimpleAction::create);
+
+resolve_instance!(TestModule, Session => try Session::open);
+
+resolve_instance!(TestModule, Box: Session => Session : try Session::open);
+
 resolve_instance!(TestModule, Box: dyn TestActionable => ConcreteActionable : ConcreteActionable::new);
 
+roots!(
+    TestModule,
+    Arc<dyn TestTrait>,
+    Arc<CyclicalA>,
+    Arc<Repository>,
+    Session,
+    Box<dyn TestActionable>
+);
+
+struct PluginModule;
+struct PluginHost {
+    plugins: PluginModule,
+}
+struct DelegatingHost {
+    plugins: PluginModule,
+}
+
+resolve_multi!(PluginModule, dyn Plugin => [PluginA::new, PluginB::new]);
+
+resolve_multi!(TestModule, dyn Plugin => [HelperPlugin::new]);
+
+resolve_multi!(PluginHost, dyn Plugin => [PluginC::new] + [plugins]);
+
+resolve_delegated!(DelegatingHost, PluginModule => plugins);
+
+/// Compose several submodules with its own rules
+struct Application {
+    plugins: PluginModule,
+    core: TestModule,
+}
+
+resolve_delegated!(Application,
+    PluginModule => plugins [Vec<Arc<dyn Plugin>>],
+    TestModule => core [Arc<dyn TestTrait>, SimpleAction]
+);
+
+resolve_singleton!(Application, HelperPlugin => HelperPlugin::new);
+
+/// Library module whose logger is replaced by the application
+struct LoggingLib;
+struct LayeredApp {
+    lib: LoggingLib,
+}
+
+resolve_singleton!(LoggingLib,
+    dyn Logger => LibLogger::new,
+    Formatter => Formatter::new,
+    Audit => Audit::new,
+    Clock => Clock::new,
+    Journal => Journal::new,
+    Tracer => Tracer::new
+);
+
+resolve_delegated!(LayeredApp, LoggingLib => lib [Arc<Formatter>, Arc<Audit>, Arc<Journal>, Arc<Tracer>] override [Arc<dyn Logger>]);
+
+resolve_singleton!(LayeredApp, dyn Logger => AppLogger::new);
+
+/// Submodules binding the same internal type with different rules
+struct StorageLib;
+struct HttpLib;
+struct Platform {
+    storage: StorageLib,
+    http: HttpLib,
+}
+struct UnifiedPlatform {
+    storage: StorageLib,
+    http: HttpLib,
+}
+
+resolve_singleton!(StorageLib, dyn Logger => LibLogger::new, Storage => Storage::new);
+
+resolve_singleton!(HttpLib, dyn Logger => MockLogger::new, Http => Http::new);
+
+resolve_delegated!(Platform,
+    StorageLib => storage [Arc<Storage>],
+    HttpLib => http [Arc<Http>]
+);
+
+resolve_delegated!(UnifiedPlatform,
+    StorageLib => storage [Arc<Storage>] override [Arc<dyn Logger>],
+    HttpLib => http [Arc<Http>] override [Arc<dyn Logger>]
+);
+
+resolve_singleton!(UnifiedPlatform, dyn Logger => LibLogger::new);
+
+resolve_map!(PluginModule, &'static str => dyn Plugin => ["a" => PluginA::new, "b" => PluginB::new]);
+
+resolve_map!(PluginHost, &'static str => dyn Plugin => ["c" => PluginC::new] + [plugins]);
+
+resolve_map!(TestModule,
+    &'static str => dyn Plugin => ["helper" => HelperPlugin::new, "helper" => PluginA::new],
+    u8 => dyn Plugin => [1 => PluginA::new, 2 => HelperPlugin::new]
+);
+
 fn is_same_ptr<T: ?Sized>(a1: &Arc<T>, a2: &Arc<T>) -> bool {
     Arc::ptr_eq(a1, a2)
 }
@@ -120,9 +765,10 @@ fn set_provider_late() {
     let v1: Arc<dyn TestTrait> = resolver.inject();
 
     let singleton: Arc<dyn TestTrait> = Arc::new(SecretImpl::default());
-    assert!(resolver
-        .set_provider(resolve::SingletonProvider::build(singleton.clone()))
-        .is_err());
+    assert!(matches!(
+        resolver.set_provider(resolve::SingletonProvider::build(singleton.clone())),
+        Err(resolve::WiringError::AlreadyResolved { .. })
+    ));
 
     let v2: Arc<dyn TestTrait> = resolver.inject();
     assert!(!is_same_ptr(&v1, &singleton));
@@ -130,8 +776,914 @@ fn set_provider_late() {
 }
 
 #[test]
-#[should_panic]
+#[should_panic(expected = "Cyclic dependencies")]
 fn detect_cyclical() {
     let resolver = Hanami::new(TestModule);
+
+    let Err(resolve::WiringError::CyclicResolution { path }) =
+        resolver.try_inject::<Arc<CyclicalA>>()
+    else {
+        panic!("The cyclical dependency was not detected");
+    };
+    assert_eq!(
+        path,
+        vec![
+            type_name::<Arc<CyclicalA>>(),
+            type_name::<Arc<CyclicalB>>(),
+            type_name::<Arc<CyclicalA>>(),
+        ]
+    );
+
     let _v1: Arc<CyclicalA> = resolver.inject();
 }
+
+#[test]
+fn try_inject_cyclical() {
+    let resolver = Hanami::new(TestModule);
+    assert!(resolver.try_inject::<Arc<CyclicalA>>().is_err());
+
+    // The failed resolution does not leave the injector in an inconsistent state
+    let Err(resolve::WiringError::CyclicResolution { path }) =
+        resolver.try_inject::<Arc<CyclicalB>>()
+    else {
+        panic!("The cyclical dependency was not detected");
+    };
+    assert_eq!(path.first(), Some(&type_name::<Arc<CyclicalB>>()));
+    assert!(resolver.try_inject::<Arc<dyn TestTrait>>().is_ok());
+}
+
+#[test]
+fn try_inject_and_call() -> Result<(), resolve::WiringError> {
+    let resolver = Hanami::new(TestModule);
+
+    let v1: Arc<dyn TestTrait> = resolver.try_inject()?;
+    let v2 = resolver.try_inject_and_call(|v: Arc<dyn TestTrait>| v)?;
+    assert!(is_same_ptr(&v1, &v2));
+
+    assert!(resolver
+        .try_inject_and_call(|_: Arc<CyclicalA>| ())
+        .is_err());
+    Ok(())
+}
+
+fn is_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn share_between_threads() {
+    is_send_sync::<Hanami<TestModule>>();
+    is_send_sync::<resolve::Provider<Box<dyn TestActionable>>>();
+    is_send_sync::<resolve::SingletonProvider<Arc<dyn TestTrait>>>();
+
+    let resolver = Arc::new(Hanami::new(TestModule));
+    let barrier = Arc::new(Barrier::new(8));
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let resolver = resolver.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                let action: Box<dyn TestActionable> = resolver.inject();
+                let helper: Arc<dyn TestTrait> = resolver.inject();
+                assert!(is_same_ptr(&action.get_helper(), &helper));
+                helper
+            })
+        })
+        .collect();
+
+    let helpers: Vec<Arc<dyn TestTrait>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    let v: Arc<dyn TestTrait> = resolver.inject();
+    assert!(helpers.iter().all(|h| is_same_ptr(h, &v)));
+}
+
+#[test]
+fn reentrant_injection() {
+    let resolver = Hanami::new(TestModule);
+
+    let (v1, v2) = resolver.inject_and_call(|v1: Arc<dyn TestTrait>| {
+        let v2: Arc<dyn TestTrait> = resolver.inject();
+        (v1, v2)
+    });
+    assert!(is_same_ptr(&v1, &v2));
+
+    let helper = resolver.inject_and_call(|_: SimpleAction| {
+        resolver.inject_and_call(|a: Box<dyn TestActionable>| a.get_helper())
+    });
+    assert!(is_same_ptr(&v1, &helper));
+}
+
+#[test]
+fn parallel_singletons() {
+    let resolver = Arc::new(Hanami::new(TestModule));
+
+    let other = resolver.clone();
+    let handle = thread::spawn(move || other.inject::<Arc<ParallelA>>());
+    let b: Arc<ParallelB> = resolver.inject();
+    let a = handle.join().unwrap();
+
+    // Both constructors were running at the same time
+    assert!(a.0 && b.0);
+}
+
+#[test]
+fn concurrent_singleton_built_once() {
+    let resolver = Arc::new(Hanami::new(TestModule));
+    let barrier = Arc::new(Barrier::new(8));
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let resolver = resolver.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                resolver.inject::<Arc<Slow>>()
+            })
+        })
+        .collect();
+
+    let instances: Vec<Arc<Slow>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert!(instances.iter().all(|s| is_same_ptr(s, &instances[0])));
+    assert_eq!(SLOW_BUILDS.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn detect_cyclical_across_threads() {
+    let resolver = Arc::new(Hanami::new(TestModule));
+    let barrier = Arc::new(Barrier::new(2));
+
+    let other = resolver.clone();
+    let other_barrier = barrier.clone();
+    let handle = thread::spawn(move || {
+        other_barrier.wait();
+        other.try_inject::<Arc<CrossB>>().map(|_| ())
+    });
+    barrier.wait();
+    let a = resolver.try_inject::<Arc<CrossA>>().map(|_| ());
+    let b = handle.join().unwrap();
+
+    for result in [a, b] {
+        let Err(resolve::WiringError::CyclicResolution { path }) = result else {
+            panic!("The cyclical dependency was not detected");
+        };
+        assert_eq!(path.first(), path.last());
+    }
+}
+
+#[test]
+fn detect_cyclical_across_scope_and_threads() {
+    let resolver = Hanami::new(TestModule);
+    let scope = Arc::new(resolver.scope());
+    let (results, received) = mpsc::channel();
+
+    let scoped = scope.clone();
+    let scoped_results = results.clone();
+    thread::spawn(move || {
+        let _ = scoped_results.send(scoped.try_inject::<Arc<ScopedCross>>().map(|_| ()));
+    });
+    let shared = scope.clone();
+    thread::spawn(move || {
+        let _ = results.send(shared.try_inject::<Arc<SharedCross>>().map(|_| ()));
+    });
+
+    // Each thread waits for a slot owned by the other one, in the scope and in the shared type map
+    for _ in 0..2 {
+        let result = received
+            .recv_timeout(Duration::from_secs(10))
+            .expect("The threads are deadlocked");
+        let Err(resolve::WiringError::CyclicResolution { path }) = result else {
+            panic!("The cyclical dependency was not detected");
+        };
+        assert_eq!(path.first(), path.last());
+    }
+}
+
+#[test]
+fn recover_from_panicking_constructor() {
+    let resolver = Hanami::new(TestModule);
+
+    let Err(resolve::WiringError::ConstructorPanicked { type_name, message }) =
+        resolver.try_inject::<Arc<FlakyUser>>()
+    else {
+        panic!("The panicking constructor was not reported");
+    };
+    assert_eq!(type_name, std::any::type_name::<Arc<Flaky>>());
+    assert_eq!(message, "first attempt fails");
+
+    // All slots were released: the next attempt can build the missing singletons
+    let _user: Arc<FlakyUser> = resolver.inject();
+}
+
+#[test]
+fn fallible_constructors() {
+    let resolver = Hanami::new(TestModule);
+
+    let _config: Arc<Config> = resolver.inject();
+
+    let Err(resolve::WiringError::Constructor { type_name, source }) =
+        resolver.try_inject::<Arc<Repository>>()
+    else {
+        panic!("The failed singleton constructor was not reported");
+    };
+    assert_eq!(type_name, std::any::type_name::<Arc<Database>>());
+    assert!(source.downcast_ref::<ConnectionRefused>().is_some());
+
+    let Err(resolve::WiringError::Constructor { type_name, source }) =
+        resolver.try_inject::<Session>()
+    else {
+        panic!("The failed instance constructor was not reported");
+    };
+    assert_eq!(type_name, std::any::type_name::<Session>());
+    assert_eq!(source.to_string(), "session expired");
+
+    assert!(resolver.try_inject::<Box<Session>>().is_err());
+}
+
+#[test]
+#[should_panic(expected = "connection refused")]
+fn fallible_constructor_panics() {
+    let resolver = Hanami::new(TestModule);
+    let _db: Arc<Database> = resolver.inject();
+}
+
+fn plugin_names(plugins: &[Arc<dyn Plugin>]) -> Vec<&'static str> {
+    plugins.iter().map(|p| p.name()).collect()
+}
+
+#[test]
+fn multibindings() {
+    let resolver = Hanami::new(TestModule);
+    let plugins: Vec<Arc<dyn Plugin>> = resolver.inject();
+    assert_eq!(plugin_names(&plugins), vec!["HelperPlugin"]);
+
+    // Elements are shared singletons
+    let again: Vec<Arc<dyn Plugin>> = resolver.inject();
+    assert!(is_same_ptr(&plugins[0], &again[0]));
+
+    // Contributions of submodules come after the elements of the parent
+    let resolver = Hanami::new(PluginHost {
+        plugins: PluginModule,
+    });
+    let plugins: Vec<Arc<dyn Plugin>> = resolver.inject();
+    assert_eq!(
+        plugin_names(&plugins),
+        vec!["PluginC", "PluginA", "PluginB"]
+    );
+
+    // Delegated modules provide their collection
+    let resolver = Hanami::new(DelegatingHost {
+        plugins: PluginModule,
+    });
+    let plugins: Vec<Arc<dyn Plugin>> = resolver.inject();
+    assert_eq!(plugin_names(&plugins), vec!["PluginA", "PluginB"]);
+}
+
+#[test]
+fn keyed_multibindings() {
+    let resolver = Hanami::new(PluginHost {
+        plugins: PluginModule,
+    });
+    let plugins: HashMap<&'static str, Arc<dyn Plugin>> = resolver.inject();
+    assert_eq!(plugins.len(), 3);
+    assert_eq!(plugins["a"].name(), "PluginA");
+    assert_eq!(plugins["c"].name(), "PluginC");
+
+    let resolver = Hanami::new(TestModule);
+    let plugins: HashMap<u8, Arc<dyn Plugin>> = resolver.inject();
+    assert_eq!(plugins[&2].name(), "HelperPlugin");
+
+    let Err(resolve::WiringError::DuplicateKey { key, .. }) =
+        resolver.try_inject::<HashMap<&'static str, Arc<dyn Plugin>>>()
+    else {
+        panic!("The duplicate key was not reported");
+    };
+    assert_eq!(key, "\"helper\"");
+}
+
+#[test]
+fn qualified_bindings() {
+    let resolver = Hanami::new(TestModule);
+    let primary: Named<Primary, Arc<dyn Plugin>> = resolver.inject();
+    let replica: Named<Replica, Arc<dyn Plugin>> = resolver.inject();
+    assert_eq!(primary.name(), "PluginA");
+    assert_eq!(replica.name(), "PluginB");
+
+    // Each qualified binding is a separate singleton
+    let replication: Arc<Replication> = resolver.inject();
+    assert!(is_same_ptr(&primary, &replication.primary));
+    assert!(is_same_ptr(&replica, &replication.replica));
+
+    let action: Named<Primary, SimpleAction> = resolver.inject();
+    action.callme();
+}
+
+#[test]
+fn delegate_to_several_submodules() {
+    let resolver = Hanami::new(Application {
+        plugins: PluginModule,
+        core: TestModule,
+    });
+    let plugins: Vec<Arc<dyn Plugin>> = resolver.inject();
+    assert_eq!(plugin_names(&plugins), vec!["PluginA", "PluginB"]);
+
+    // Direct rules of the parent can use the delegated types
+    let helper: Arc<HelperPlugin> = resolver.inject();
+    assert_eq!(helper.name(), "HelperPlugin");
+
+    let action: SimpleAction = resolver.inject();
+    action.callme();
+}
+
+#[test]
+fn conflicting_submodule_rules() {
+    // Both submodules resolve the same logger type with their own rule
+    let resolver = Hanami::new(Platform {
+        storage: StorageLib,
+        http: HttpLib,
+    });
+    let storage: Arc<Storage> = resolver.inject();
+    assert_eq!(storage.logger.origin(), "library");
+    assert!(matches!(
+        resolver.try_inject::<Arc<Http>>(),
+        Err(resolve::WiringError::ConflictingRules { .. })
+    ));
+
+    // The conflict does not depend on the resolution order
+    let resolver = Hanami::new(Platform {
+        storage: StorageLib,
+        http: HttpLib,
+    });
+    let http: Arc<Http> = resolver.inject();
+    assert_eq!(http.logger.origin(), "mock");
+    assert!(matches!(
+        resolver.try_inject::<Arc<Storage>>(),
+        Err(resolve::WiringError::ConflictingRules { .. })
+    ));
+
+    // The parent can resolve the conflict by overriding the shared type in both submodules
+    let resolver = Hanami::new(UnifiedPlatform {
+        storage: StorageLib,
+        http: HttpLib,
+    });
+    let http: Arc<Http> = resolver.inject();
+    let storage: Arc<Storage> = resolver.inject();
+    assert!(is_same_ptr(&http.logger, &storage.logger));
+}
+
+#[test]
+fn override_layers() {
+    let resolver = Hanami::new(LoggingLib);
+    let audit: Arc<Audit> = resolver.inject();
+    assert_eq!(audit.logger.origin(), "library");
+
+    // The overriding logger is used by the internal rules of the submodule
+    let resolver = Hanami::new(LayeredApp { lib: LoggingLib });
+    let audit: Arc<Audit> = resolver.inject();
+    assert_eq!(audit.logger.origin(), "application");
+    let logger: Arc<dyn Logger> = resolver.inject();
+    assert!(is_same_ptr(&logger, &audit.logger));
+
+    // The overriding logger depends on a delegated type
+    let resolver = Hanami::new(LayeredApp { lib: LoggingLib });
+    let _: Arc<Formatter> = resolver.inject();
+    let logger: Arc<dyn Logger> = resolver.inject();
+    assert_eq!(logger.origin(), "application");
+}
+
+#[test]
+fn override_layers_during_replacement() -> Result<(), resolve::WiringError> {
+    let resolver = Arc::new(Hanami::new(LayeredApp { lib: LoggingLib }));
+    let building = resolver.clone();
+    let handle = thread::spawn(move || building.inject::<Arc<Journal>>());
+
+    // The overriding logger is discarded while the submodule builds the journal:
+    // the submodule still resolves it with the rule of the parent
+    assert!(rendezvous(&JOURNAL_STARTED));
+    resolver.replace_provider(resolve::SingletonProvider::build(Arc::new(Formatter)))?;
+    assert!(rendezvous(&JOURNAL_RESUMED));
+    assert_eq!(handle.join().unwrap().logger.origin(), "application");
+
+    let logger: Arc<dyn Logger> = resolver.inject();
+    assert_eq!(logger.origin(), "application");
+    Ok(())
+}
+
+#[test]
+fn lazy_injection() {
+    let resolver = Hanami::new(TestModule);
+    let frugal: Arc<Frugal> = resolver.inject();
+    assert_eq!(EXPENSIVE_BUILDS.load(Ordering::SeqCst), 0);
+    let expensive = frugal.expensive.get();
+    assert_eq!(EXPENSIVE_BUILDS.load(Ordering::SeqCst), 1);
+    assert!(is_same_ptr(&expensive, &resolver.inject()));
+
+    // Lazy on-demand instances are created on each call
+    let actions: Lazy<SimpleAction> = resolver.inject();
+    actions.get().callme();
+    actions.get().callme();
+
+    // Lazy parameters can not be resolved without their injector
+    let lazy: Lazy<Arc<Expensive>> = Hanami::new(TestModule).inject();
+    assert!(matches!(
+        lazy.try_get(),
+        Err(resolve::WiringError::InjectorDropped { .. })
+    ));
+}
+
+#[test]
+fn lazy_breaks_cycles() {
+    let resolver = Hanami::new(TestModule);
+    let ping: Arc<Ping> = resolver.inject();
+    let pong = ping.pong.get();
+    assert!(is_same_ptr(&ping, &pong.ping));
+}
+
+#[test]
+fn lazy_delegation() {
+    // Lazy parameters of submodules are resolved by the submodule
+    let resolver = Hanami::new(DelegatingHost {
+        plugins: PluginModule,
+    });
+    let plugins: Lazy<Vec<Arc<dyn Plugin>>> = resolver.inject();
+    assert_eq!(plugin_names(&plugins.get()), vec!["PluginA", "PluginB"]);
+
+    // Lazy parameters of delegated types use the overriding rules of the parent
+    let resolver = Hanami::new(LayeredApp { lib: LoggingLib });
+    let audit: Lazy<Arc<Audit>> = resolver.inject();
+    assert_eq!(audit.get().logger.origin(), "application");
+}
+
+#[test]
+fn lazy_override_layers() -> Result<(), resolve::WiringError> {
+    // Lazy parameters created by a submodule also use the overriding rules of the parent,
+    // even if the overriding provider was discarded in the meantime
+    let resolver = Hanami::new(LayeredApp { lib: LoggingLib });
+    let tracer: Arc<Tracer> = resolver.inject();
+    resolver.replace_provider(resolve::SingletonProvider::build(Arc::new(Formatter)))?;
+    assert_eq!(tracer.logger.get().origin(), "application");
+
+    let logger: Arc<dyn Logger> = resolver.inject();
+    assert_eq!(logger.origin(), "application");
+    assert!(is_same_ptr(&logger, &tracer.logger.get()));
+    Ok(())
+}
+
+#[test]
+fn inject_providers() {
+    let resolver = Hanami::new(TestModule);
+    let runner: Arc<ActionRunner> = resolver.inject();
+    runner.run(3);
+
+    // A provider of singletons always provides the same instance
+    let helpers: Provider<Arc<dyn TestTrait>> = resolver.inject();
+    assert!(is_same_ptr(&helpers.provide(), &resolver.inject()));
+
+    // Failures of fallible constructors are reported by the provider
+    let sessions: Provider<Session> = resolver.inject();
+    assert!(matches!(
+        sessions.try_provide(),
+        Err(resolve::WiringError::Constructor { .. })
+    ));
+}
+
+#[test]
+fn assisted_injection() {
+    let resolver = Hanami::new(TestModule);
+    let handlers: Factory<(UserId, Payload), Handler> = resolver.inject();
+    let first = handlers.create((UserId(1), Payload("first")));
+    let second = handlers.create((UserId(2), Payload("second")));
+    assert_eq!((first.user, first.payload), (1, "first"));
+    assert_eq!((second.user, second.payload), (2, "second"));
+    assert!(is_same_ptr(&first.helper, &second.helper));
+
+    let anonymous: Factory<(Payload,), Handler> = resolver.inject();
+    assert_eq!(anonymous.create((Payload("data"),)).user, 0);
+    assert!(matches!(
+        anonymous.try_create((Payload(""),)),
+        Err(resolve::WiringError::Constructor { .. })
+    ));
+}
+
+#[test]
+fn scoped_bindings() {
+    let resolver = Hanami::new(TestModule);
+    assert!(matches!(
+        resolver.try_inject::<Arc<Transaction>>(),
+        Err(resolve::WiringError::OutOfScope { .. })
+    ));
+
+    let scope = resolver.scope();
+    let tx: Arc<Transaction> = scope.inject();
+    assert!(is_same_ptr(&tx, &scope.inject()));
+
+    // On-demand instances use the instance of their scope
+    let work: UnitOfWork = scope.inject();
+    assert!(is_same_ptr(&tx, &work.tx));
+    let other: UnitOfWork = resolver.scope().inject();
+    assert_ne!(tx.0, other.tx.0);
+
+    // Singletons are shared with the scopes but can not depend on scoped bindings
+    let helper: Arc<dyn TestTrait> = scope.inject();
+    assert!(is_same_ptr(&helper, &resolver.inject()));
+    assert!(matches!(
+        scope.try_inject::<Arc<TransactionLog>>(),
+        Err(resolve::WiringError::ScopedDependency { .. })
+    ));
+
+    // Scoped instances are dropped with their scope
+    let weak = Arc::downgrade(&tx);
+    drop((tx, work, scope));
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn override_scoped_bindings() -> Result<(), resolve::WiringError> {
+    let mut resolver = Hanami::new(TestModule);
+    let tx = Arc::new(Transaction(999));
+    assert!(matches!(
+        resolver.set_provider(resolve::SingletonProvider::build(tx.clone())),
+        Err(resolve::WiringError::ScopedOverride { .. })
+    ));
+    assert!(matches!(
+        HanamiBuilder::new(TestModule)
+            .provider(resolve::SingletonProvider::build(tx.clone()))
+            .build(),
+        Err(resolve::WiringError::ScopedOverride { .. })
+    ));
+
+    let mut scope = resolver.scope();
+    scope.set_provider(resolve::SingletonProvider::build(tx.clone()))?;
+    let work: UnitOfWork = scope.inject();
+    assert_eq!(work.tx.0, 999);
+    Ok(())
+}
+
+#[test]
+fn thread_local_bindings() {
+    let resolver = Arc::new(Hanami::new(TestModule));
+    let helper: Arc<dyn TestTrait> = resolver.inject();
+
+    let workers: Vec<_> = (0..2)
+        .map(|_| {
+            let resolver = resolver.clone();
+            let helper = helper.clone();
+            thread::spawn(move || {
+                let buffer: Rc<Buffer> = resolver.inject();
+                buffer.data.borrow_mut().push(1);
+                let again: Rc<Buffer> = resolver.inject();
+                assert!(Rc::ptr_eq(&buffer, &again));
+                assert_eq!(again.data.borrow().len(), 1);
+                // Thread-local components can depend on singletons
+                assert!(is_same_ptr(&buffer.helper, &helper));
+                buffer.id
+            })
+        })
+        .collect();
+    let ids: Vec<usize> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+    assert_ne!(ids[0], ids[1]);
+
+    // Instances are dropped when their thread exits
+    assert_eq!(DROPPED_BUFFERS.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn thread_local_instances_dropped_with_injector() {
+    for _ in 0..100 {
+        let resolver = Hanami::new(TestModule);
+        let _: Rc<Cache> = resolver.inject();
+    }
+    assert_eq!(DROPPED_CACHES.load(Ordering::SeqCst), 100);
+
+    // Other threads drop their instance on their next use of a thread-local provider
+    let resolver = Arc::new(Hanami::new(TestModule));
+    let (used, dropped) = (Arc::new(Barrier::new(2)), Arc::new(Barrier::new(2)));
+    let worker = {
+        let resolver = resolver.clone();
+        let (used, dropped) = (used.clone(), dropped.clone());
+        thread::spawn(move || {
+            let _: Rc<Cache> = resolver.inject();
+            drop(resolver);
+            used.wait();
+            dropped.wait();
+            let other = Hanami::new(TestModule);
+            let _: Rc<Cache> = other.inject();
+            DROPPED_CACHES.load(Ordering::SeqCst)
+        })
+    };
+    used.wait();
+    drop(resolver);
+    dropped.wait();
+    assert_eq!(worker.join().unwrap(), 101);
+}
+
+#[test]
+fn child_injectors() -> Result<(), resolve::WiringError> {
+    let resolver = Hanami::new(LoggingLib);
+    let formatter: Arc<Formatter> = resolver.inject();
+    let audit: Arc<Audit> = resolver.inject();
+
+    let mut child = resolver.child();
+    let mock: Arc<dyn Logger> = Arc::new(MockLogger);
+    child.set_provider(resolve::SingletonProvider::build(mock.clone()))?;
+
+    // Providers depending on the override are resolved again in the child only
+    let child_audit: Arc<Audit> = child.inject();
+    assert!(is_same_ptr(&child_audit.logger, &mock));
+    assert!(!is_same_ptr(&child_audit, &audit));
+    assert_eq!(audit.logger.origin(), "library");
+
+    // Other providers are inherited
+    assert!(is_same_ptr(&formatter, &child.inject()));
+
+    // Inherited providers can no longer be overridden
+    let mut child = resolver.child();
+    let _: Arc<Audit> = child.inject();
+    assert!(child
+        .set_provider(resolve::SingletonProvider::build(mock))
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn child_injectors_with_lazy_parameters() -> Result<(), resolve::WiringError> {
+    let resolver = Hanami::new(TestModule);
+    let frugal: Arc<Frugal> = resolver.inject();
+
+    // Providers holding a lazy parameter depend on its target type
+    let mut child = resolver.child();
+    let expensive = Arc::new(Expensive);
+    child.set_provider(resolve::SingletonProvider::build(expensive.clone()))?;
+    let child_frugal: Arc<Frugal> = child.inject();
+    assert!(!is_same_ptr(&child_frugal, &frugal));
+    assert!(is_same_ptr(&child_frugal.expensive.get(), &expensive));
+    Ok(())
+}
+
+#[test]
+fn replace_provider() -> Result<(), resolve::WiringError> {
+    let resolver = Hanami::new(LayeredApp { lib: LoggingLib });
+    let audit: Arc<Audit> = resolver.inject();
+    let logger: Arc<dyn Logger> = resolver.inject();
+    assert_eq!(audit.logger.origin(), "application");
+
+    // Audit depends on Formatter through the application logger
+    resolver.replace_provider(resolve::SingletonProvider::build(Arc::new(Formatter)))?;
+    let rebuilt: Arc<Audit> = resolver.inject();
+    assert!(!is_same_ptr(&rebuilt, &audit));
+    assert!(!is_same_ptr(&rebuilt.logger, &logger));
+    assert!(is_same_ptr(&rebuilt, &resolver.inject()));
+
+    let mock: Arc<dyn Logger> = Arc::new(MockLogger);
+    resolver.replace_provider(resolve::SingletonProvider::build(mock.clone()))?;
+    let mocked: Arc<Audit> = resolver.inject();
+    assert!(is_same_ptr(&mocked.logger, &mock));
+
+    // Providers which do not depend on the replaced type are kept
+    let formatter: Arc<Formatter> = resolver.inject();
+    resolver.replace_provider(resolve::SingletonProvider::build(
+        Arc::new(MockLogger) as Arc<dyn Logger>
+    ))?;
+    assert!(is_same_ptr(&formatter, &resolver.inject()));
+    Ok(())
+}
+
+#[test]
+fn replace_provider_during_resolution() -> Result<(), resolve::WiringError> {
+    let resolver = Arc::new(Hanami::new(TestModule));
+    let building = resolver.clone();
+    let handle = thread::spawn(move || building.inject::<Arc<Reloadable>>());
+
+    // The dependent is being built with the previous provider
+    assert!(rendezvous(&RELOAD_STARTED));
+    resolver.replace_provider(resolve::SingletonProvider::build(Arc::new(Setting(
+        "reloaded",
+    ))))?;
+    assert!(rendezvous(&RELOAD_REPLACED));
+    assert_eq!(handle.join().unwrap().setting.0, "initial");
+
+    let reloaded: Arc<Reloadable> = resolver.inject();
+    assert_eq!(reloaded.setting.0, "reloaded");
+    assert!(is_same_ptr(&reloaded, &resolver.inject()));
+    Ok(())
+}
+
+#[test]
+fn dependency_graph() {
+    let resolver = Hanami::new(LayeredApp { lib: LoggingLib });
+    assert!(resolver.graph().nodes().is_empty());
+    let _: Arc<Audit> = resolver.inject();
+
+    let graph = resolver.graph();
+    let audit = graph.node::<Arc<Audit>>().unwrap();
+    assert_eq!(audit.lifetime(), resolve::Lifetime::Singleton);
+    let logger = graph.node::<Arc<dyn Logger>>().unwrap();
+    let deps: Vec<_> = graph.dependencies(audit).map(|n| n.type_name()).collect();
+    assert_eq!(deps, [logger.type_name()]);
+    let deps: Vec<_> = graph.dependencies(logger).map(|n| n.type_name()).collect();
+    assert_eq!(deps, [std::any::type_name::<Arc<Formatter>>()]);
+
+    // Dependencies are created first
+    let names: Vec<_> = graph.nodes().iter().map(|n| n.type_name()).collect();
+    assert_eq!(
+        names,
+        [
+            std::any::type_name::<Arc<Formatter>>(),
+            std::any::type_name::<Arc<dyn Logger>>(),
+            std::any::type_name::<Arc<Audit>>(),
+        ]
+    );
+
+    let resolver = Hanami::new(TestModule);
+    let _: Box<dyn TestActionable> = resolver.inject();
+    let graph = resolver.graph();
+    let actionable = graph.node::<Box<dyn TestActionable>>().unwrap();
+    assert_eq!(actionable.lifetime(), resolve::Lifetime::Transient);
+    assert_eq!(actionable.dependencies().len(), 1);
+}
+
+#[test]
+fn export_graph() -> Result<(), resolve::WiringError> {
+    let mut resolver = Hanami::new(LoggingLib);
+    let mock: Arc<dyn Logger> = Arc::new(MockLogger);
+    resolver.set_provider(resolve::SingletonProvider::build(mock))?;
+    let _: Arc<Audit> = resolver.inject();
+
+    assert_eq!(
+        resolver.export_dot(),
+        r#"digraph hanami {
+    n0 [label="alloc::sync::Arc<dyn hanami::tests::Logger>", shape=box, style="filled", fillcolor=lightgrey];
+    n1 [label="alloc::sync::Arc<hanami::tests::Audit>", shape=box];
+    n1 -> n0;
+}
+"#
+    );
+    assert_eq!(
+        resolver.export_mermaid(),
+        r#"graph TD
+    n0["alloc::sync::Arc#lt;dyn hanami::tests::Logger#gt;"]
+    n1["alloc::sync::Arc#lt;hanami::tests::Audit#gt;"]
+    n1 --> n0
+    classDef overridden fill:#d3d3d3
+    class n0 overridden
+"#
+    );
+
+    let resolver = Hanami::new(TestModule);
+    let _: Box<dyn TestActionable> = resolver.inject();
+    assert!(resolver.export_dot().contains(
+        "[label=\"alloc::boxed::Box<dyn hanami::tests::TestActionable>\", shape=ellipse];"
+    ));
+    Ok(())
+}
+
+#[test]
+fn json_report() -> Result<(), resolve::WiringError> {
+    let mut resolver = Hanami::new(LoggingLib);
+    assert_eq!(resolver.export_json(), "{\n  \"types\": []\n}\n");
+    let mock: Arc<dyn Logger> = Arc::new(MockLogger);
+    resolver.set_provider(resolve::SingletonProvider::build(mock))?;
+    let _: Arc<Audit> = resolver.inject();
+    let _: Arc<Audit> = resolver.inject();
+
+    let graph = resolver.graph();
+    let audit = graph.node::<Arc<Audit>>().unwrap();
+    assert_eq!(audit.provided(), 2);
+    let duration = audit.duration().unwrap().as_nanos();
+    assert_eq!(
+        resolver.export_json(),
+        format!(
+            r#"{{
+  "types": [
+    {{
+      "type_name": "alloc::sync::Arc<dyn hanami::tests::Logger>",
+      "lifetime": "Singleton",
+      "origin": "override",
+      "dependencies": [],
+      "duration_ns": null,
+      "provided": 1
+    }},
+    {{
+      "type_name": "alloc::sync::Arc<hanami::tests::Audit>",
+      "lifetime": "Singleton",
+      "origin": "rule",
+      "dependencies": ["alloc::sync::Arc<dyn hanami::tests::Logger>"],
+      "duration_ns": {},
+      "provided": 2
+    }}
+  ]
+}}
+"#,
+            duration
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn validate_roots() {
+    let resolver = Hanami::new(TestModule);
+    let errors = resolver.warm_up();
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(
+        errors[0],
+        resolve::WiringError::CyclicResolution { .. }
+    ));
+    assert!(matches!(
+        errors[1],
+        resolve::WiringError::Constructor { .. }
+    ));
+    assert!(resolver.graph().node::<Arc<dyn TestTrait>>().is_some());
+
+    // On-demand instances are also provided
+    let errors = resolver.validate();
+    assert_eq!(errors.len(), 3);
+    assert_eq!(
+        errors[2].to_string(),
+        format!(
+            "Constructor failed while resolving {}: session expired",
+            type_name::<Session>()
+        )
+    );
+}
+
+#[test]
+fn eager_singletons() {
+    let resolver = HanamiBuilder::new(TestModule)
+        .eager::<Arc<Scheduler>>()
+        .build()
+        .unwrap();
+    assert_eq!(SCHEDULERS.load(Ordering::SeqCst), 1);
+
+    // Dependencies are constructed first
+    let names: Vec<_> = resolver
+        .graph()
+        .nodes()
+        .iter()
+        .map(|n| n.type_name())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            type_name::<Arc<dyn TestTrait>>(),
+            type_name::<Arc<Scheduler>>()
+        ]
+    );
+    let _: Arc<Scheduler> = resolver.inject();
+    assert_eq!(SCHEDULERS.load(Ordering::SeqCst), 1);
+
+    let built = HanamiBuilder::new(TestModule)
+        .eager::<Arc<CyclicalA>>()
+        .build();
+    assert!(matches!(
+        built,
+        Err(resolve::WiringError::CyclicResolution { .. })
+    ));
+}
+
+#[test]
+fn configure_injector() -> Result<(), resolve::WiringError> {
+    let resolved = Arc::new(Mutex::new(Vec::new()));
+    let events = resolved.clone();
+    let mock: Arc<dyn Logger> = Arc::new(MockLogger);
+    let resolver = HanamiBuilder::new(LoggingLib)
+        .provider(resolve::SingletonProvider::build(mock.clone()))
+        .listener(move |event| {
+            if let ResolutionEvent::Resolved { type_name, .. } = event {
+                events.lock().unwrap().push(*type_name);
+            }
+        })
+        .strict()
+        .build()?;
+
+    let audit: Arc<Audit> = resolver.inject();
+    assert!(is_same_ptr(&audit.logger, &mock));
+    assert_eq!(*resolved.lock().unwrap(), [type_name::<Arc<Audit>>()]);
+
+    // Providers can no longer be overridden
+    let mut child = resolver.child();
+    assert!(matches!(
+        child.set_provider(resolve::SingletonProvider::build(mock.clone())),
+        Err(resolve::WiringError::StrictMode { .. })
+    ));
+    assert!(matches!(
+        resolver.replace_provider(resolve::SingletonProvider::build(mock.clone())),
+        Err(resolve::WiringError::StrictMode { .. })
+    ));
+
+    let built = HanamiBuilder::new(LoggingLib)
+        .provider(resolve::SingletonProvider::build(mock.clone()))
+        .provider(resolve::SingletonProvider::build(mock))
+        .build();
+    assert!(matches!(
+        built,
+        Err(resolve::WiringError::DuplicateOverride { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "Cyclic dependencies")]
+fn panic_on_cycles() {
+    let resolver = HanamiBuilder::new(TestModule)
+        .cycles(CycleBehaviour::Panic)
+        .build()
+        .unwrap();
+    let _ = resolver.try_inject::<Arc<CyclicalA>>();
+}
@@ -0,0 +1,595 @@
+//! Storage of providers shared between all threads using the same injector.
+//!
+//! Each type has its own slot with a once-style initialization: the map itself is only locked
+//! to find or create slots, and a thread waiting for a type being resolved by another thread
+//! only blocks on the slot of this type. Independent types can then be resolved in parallel.
+//!
+//! Each stored provider records the types of its direct dependencies. A child type map uses them to
+//! inherit the providers of its parent which do not depend on providers overridden in the child,
+//! and a replaced provider invalidates the providers depending on it.
+//! Together with the lifetime and the creation order of the providers, they describe the dependency
+//! graph of the injector (see [crate::graph]). Stored providers also record their construction duration
+//! and count the instances they provide.
+
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLock};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+use crate::resolve::{Lifetime, Provide, Provider, WiringError};
+
+/// Stack of types being resolved by the current thread
+pub(crate) type ResolutionStack = [(TypeId, &'static str)];
+
+/// Key of the target type in the type map
+pub(crate) fn type_key<T: 'static>() -> TypeId {
+    TypeId::of::<Provider<T>>()
+}
+
+/// Store providers of [Any] type
+///
+/// Stored values must be [Send] and [Sync] to share the map between threads.
+#[derive(Default)]
+pub(crate) struct TypeMap {
+    slots: RwLock<HashMap<TypeId, Arc<Slot>>>,
+    /// Slot awaited by each blocked thread, used to detect deadlocks.
+    ///
+    /// It is shared with the type maps of the scopes, as a thread can wait for a slot of a scope
+    /// while owning a slot of the shared type map and conversely
+    waiting: Arc<Mutex<HashMap<ThreadId, Arc<Slot>>>>,
+    /// Type map providing the inherited providers
+    parent: Option<Arc<TypeMap>>,
+    /// Number of replaced providers
+    replacements: AtomicUsize,
+    /// Number of providers being replaced
+    replacing: AtomicUsize,
+}
+
+/// Storage for the provider of a single type
+pub(crate) struct Slot {
+    tid: TypeId,
+    type_name: &'static str,
+    state: Mutex<SlotState>,
+    changed: Condvar,
+}
+
+enum SlotState {
+    Vacant,
+    Resolving(ThreadId),
+    Ready(Entry),
+}
+
+/// Creation order of the stored providers, shared by all type maps
+static NEXT_ENTRY: AtomicUsize = AtomicUsize::new(0);
+
+/// Stored provider and the types of its direct dependencies
+#[derive(Clone)]
+pub(crate) struct Entry {
+    provider: Arc<dyn Any + Send + Sync>,
+    pub(crate) deps: Vec<TypeId>,
+    pub(crate) lifetime: Lifetime,
+    /// Position of the provider in the creation order
+    pub(crate) order: usize,
+    /// Set for providers inherited from the parent type map
+    inherited: bool,
+    /// Set for providers set by the user instead of a resolution rule
+    pub(crate) overridden: bool,
+    /// Resolver module whose rule built the provider, if known
+    module: Option<TypeId>,
+    /// Time spent building the provider, including the resolution of its dependencies
+    pub(crate) duration: Option<Duration>,
+    /// Number of instances provided
+    pub(crate) calls: Arc<AtomicUsize>,
+}
+
+/// Provider counting the instances it provides
+struct Counted<T> {
+    provider: Provider<T>,
+    calls: Arc<AtomicUsize>,
+}
+
+impl<T> Provide<T> for Counted<T> {
+    fn provide(&self) -> T {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.provider.provide()
+    }
+
+    fn try_provide(&self) -> Result<T, WiringError> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.provider.try_provide()
+    }
+}
+
+impl Entry {
+    /// Create the entry of a provider.
+    ///
+    /// Return the entry and the stored provider, which counts the provided instances
+    pub(crate) fn new<T: 'static>(
+        provider: Provider<T>,
+        deps: Vec<TypeId>,
+        lifetime: Lifetime,
+    ) -> (Self, Provider<T>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted: Provider<T> = Arc::new(Counted {
+            provider,
+            calls: calls.clone(),
+        });
+        let entry = Self {
+            provider: Arc::new(counted.clone()),
+            deps,
+            lifetime,
+            order: NEXT_ENTRY.fetch_add(1, Ordering::Relaxed),
+            inherited: false,
+            overridden: false,
+            module: None,
+            duration: None,
+            calls,
+        };
+        (entry, counted)
+    }
+
+    /// Entry of a provider set by the user, it has no recorded dependencies
+    pub(crate) fn overriding<T: 'static>(provider: Provider<T>, lifetime: Lifetime) -> Self {
+        let (entry, _) = Self::new(provider, Vec::new(), lifetime);
+        Self {
+            overridden: true,
+            ..entry
+        }
+    }
+}
+
+/// Lock a mutex of the type map.
+///
+/// User code never runs while holding these locks and all updates are single assignments:
+/// the content of a poisoned lock is still consistent and the poisoning can be cleared.
+fn lock<V>(mutex: &Mutex<V>) -> MutexGuard<'_, V> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Dependency chain of a cyclic resolution.
+///
+/// The chain ends with a type which is already being resolved by the current thread.
+/// It starts at the first resolution of this type in the current stack.
+fn cyclic_path(stack: &ResolutionStack, chain: &ResolutionStack) -> Vec<&'static str> {
+    let start = chain
+        .last()
+        .and_then(|(tid, _)| stack.iter().position(|(t, _)| t == tid))
+        .unwrap_or(stack.len());
+    stack[start..]
+        .iter()
+        .chain(chain.iter())
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+impl TypeMap {
+    /// Create an empty type map inheriting the providers of a parent
+    pub(crate) fn child(parent: Arc<TypeMap>) -> Self {
+        Self {
+            parent: Some(parent),
+            ..Self::default()
+        }
+    }
+
+    /// Create an empty type map for a scope, sharing the deadlock detection of this type map
+    pub(crate) fn scope(&self) -> Self {
+        Self {
+            waiting: self.waiting.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Find or create the slot of the target type
+    pub(crate) fn slot<T: 'static>(&self) -> Arc<Slot> {
+        self.slot_by_key(type_key::<T>(), type_name::<T>())
+    }
+
+    fn slot_by_key(&self, tid: TypeId, type_name: &'static str) -> Arc<Slot> {
+        if let Some(slot) = self.find(tid) {
+            return slot;
+        }
+        self.slots
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(tid)
+            .or_insert_with(|| Arc::new(Slot::new(tid, type_name)))
+            .clone()
+    }
+
+    fn find(&self, tid: TypeId) -> Option<Arc<Slot>> {
+        self.slots
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&tid)
+            .cloned()
+    }
+
+    /// Snapshot of all the slots, the map is not locked while using them
+    fn all_slots(&self) -> Vec<Arc<Slot>> {
+        self.slots
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Collect the stored providers with the name of their type
+    pub(crate) fn entries(&self) -> Vec<(TypeId, &'static str, Entry)> {
+        self.all_slots()
+            .iter()
+            .filter_map(|slot| match &*lock(&slot.state) {
+                SlotState::Ready(entry) => Some((slot.tid, slot.type_name, entry.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Find a stored provider, including the providers which can be inherited from the parent
+    fn entry(&self, tid: TypeId) -> Option<(&'static str, Entry)> {
+        if let Some(slot) = self.find(tid) {
+            if let SlotState::Ready(entry) = &*lock(&slot.state) {
+                return Some((slot.type_name, entry.clone()));
+            }
+        }
+        self.inherit(tid)
+    }
+
+    /// Inherit the provider of the parent for a vacant slot, together with all its dependencies.
+    ///
+    /// Return [None] if the provider or one of its (transitive) dependencies was overridden or built
+    /// again in this type map: the provider must then be built again.
+    fn inherit(&self, tid: TypeId) -> Option<(&'static str, Entry)> {
+        let parent = self.parent.as_ref()?;
+        let mut inherited = Vec::new();
+        if !self.collect(parent, tid, &mut inherited) {
+            return None;
+        }
+        for (t, name, mut entry) in inherited {
+            entry.inherited = true;
+            self.slot_by_key(t, name).adopt(entry);
+        }
+        let slot = self.find(tid)?;
+        let SlotState::Ready(entry) = &*lock(&slot.state) else {
+            return None;
+        };
+        Some((slot.type_name, entry.clone()))
+    }
+
+    /// Collect the entries of the parent needed to inherit a provider
+    fn collect(
+        &self,
+        parent: &TypeMap,
+        tid: TypeId,
+        inherited: &mut Vec<(TypeId, &'static str, Entry)>,
+    ) -> bool {
+        if inherited.iter().any(|(t, _, _)| *t == tid) {
+            return true;
+        }
+        if let Some(slot) = self.find(tid) {
+            match &*lock(&slot.state) {
+                SlotState::Vacant => (),
+                SlotState::Ready(entry) if entry.inherited => return true,
+                _ => return false,
+            }
+        }
+        let Some((name, entry)) = parent.entry(tid) else {
+            return false;
+        };
+        let deps = entry.deps.clone();
+        inherited.push((tid, name, entry));
+        deps.iter().all(|d| self.collect(parent, *d, inherited))
+    }
+
+    /// Retrieve the provider stored in a slot for a rule of the given module, or claim it for the current thread.
+    ///
+    /// If another thread is resolving the same type, wait until it fills or releases the slot.
+    /// Return [None] if the slot was claimed: it must then be filled or released.
+    /// Return an error if the slot is part of a cyclic resolution, including across threads,
+    /// or if the stored provider was built by the rule of another module.
+    pub(crate) fn claim<T: 'static>(
+        &self,
+        slot: &Arc<Slot>,
+        stack: &ResolutionStack,
+        module: Option<TypeId>,
+    ) -> Result<Option<Provider<T>>, WiringError> {
+        if self.parent.is_some() && !slot.is_ready() {
+            if let Some((_, entry)) = self.inherit(slot.tid) {
+                return slot.resolved_by::<T>(&entry, module).map(Some);
+            }
+        }
+
+        let me = thread::current().id();
+        loop {
+            let mut state = lock(&slot.state);
+            match &*state {
+                SlotState::Ready(entry) => return slot.resolved_by::<T>(entry, module).map(Some),
+                SlotState::Vacant => {
+                    *state = SlotState::Resolving(me);
+                    return Ok(None);
+                }
+                SlotState::Resolving(owner) if *owner == me => {
+                    let chain = [(slot.tid, slot.type_name)];
+                    return Err(WiringError::CyclicResolution {
+                        path: cyclic_path(stack, &chain),
+                    });
+                }
+                SlotState::Resolving(_) => {
+                    drop(state);
+                    self.wait(slot, me, stack)?;
+                }
+            }
+        }
+    }
+
+    /// Replace the provider of the target type, then release the slots of all the providers
+    /// which depend on it (directly or transitively): they are built again on their next resolution.
+    ///
+    /// If another thread is resolving the target type, wait until it fills or releases the slot.
+    pub(crate) fn replace<T: 'static>(
+        &self,
+        provider: Provider<T>,
+        lifetime: Lifetime,
+    ) -> Result<(), WiringError> {
+        let slot = self.slot::<T>();
+        let me = thread::current().id();
+        let mut state = lock(&slot.state);
+        loop {
+            match &*state {
+                SlotState::Resolving(owner) if *owner == me => {
+                    return Err(WiringError::ReplacedWhileResolving {
+                        type_name: slot.type_name,
+                    });
+                }
+                SlotState::Resolving(_) => {
+                    state = slot
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                _ => break,
+            }
+        }
+        self.replacing.fetch_add(1, Ordering::SeqCst);
+        self.replacements.fetch_add(1, Ordering::SeqCst);
+        *state = SlotState::Ready(Entry::overriding(provider, lifetime));
+        slot.changed.notify_all();
+        drop(state);
+
+        self.invalidate(slot.tid);
+        self.replacing.fetch_sub(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Generation of the providers built from now on, or [None] while a provider is being replaced.
+    ///
+    /// A provider being built can not be invalidated as it is not stored yet: it must only be stored
+    /// if no provider was replaced during its construction, see [TypeMap::is_current].
+    pub(crate) fn generation(&self) -> Option<usize> {
+        let generation = self.replacements.load(Ordering::SeqCst);
+        (self.replacing.load(Ordering::SeqCst) == 0).then_some(generation)
+    }
+
+    /// Whether no provider was replaced since the given generation
+    pub(crate) fn is_current(&self, generation: Option<usize>) -> bool {
+        generation == Some(self.replacements.load(Ordering::SeqCst))
+    }
+
+    /// Release the slots of all the providers depending (directly or transitively) on a type
+    fn invalidate(&self, tid: TypeId) {
+        let slots = self.all_slots();
+        let mut invalid = vec![tid];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for slot in &slots {
+                let mut state = lock(&slot.state);
+                let SlotState::Ready(entry) = &*state else {
+                    continue;
+                };
+                if entry.deps.iter().any(|d| invalid.contains(d)) {
+                    invalid.push(slot.tid);
+                    *state = SlotState::Vacant;
+                    slot.changed.notify_all();
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// Wait until another thread fills or releases a slot.
+    ///
+    /// Return an error instead of waiting if the owner of the slot is (transitively)
+    /// waiting for a slot owned by the current thread.
+    fn wait(
+        &self,
+        slot: &Arc<Slot>,
+        me: ThreadId,
+        stack: &ResolutionStack,
+    ) -> Result<(), WiringError> {
+        let mut waiting = lock(&self.waiting);
+        if let Some(chain) = Self::deadlock(&waiting, slot, me) {
+            return Err(WiringError::CyclicResolution {
+                path: cyclic_path(stack, &chain),
+            });
+        }
+        waiting.insert(me, slot.clone());
+        drop(waiting);
+
+        let mut state = lock(&slot.state);
+        while matches!(*state, SlotState::Resolving(owner) if owner != me) {
+            state = slot
+                .changed
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        drop(state);
+
+        lock(&self.waiting).remove(&me);
+        Ok(())
+    }
+
+    /// Follow the owners of awaited slots to find a chain ending on a slot owned by the current thread
+    fn deadlock(
+        waiting: &HashMap<ThreadId, Arc<Slot>>,
+        slot: &Arc<Slot>,
+        me: ThreadId,
+    ) -> Option<Vec<(TypeId, &'static str)>> {
+        let mut chain = Vec::new();
+        let mut current = slot.clone();
+        while chain.len() <= waiting.len() {
+            chain.push((current.tid, current.type_name));
+            let owner = match &*lock(&current.state) {
+                SlotState::Resolving(owner) => *owner,
+                _ => return None,
+            };
+            if owner == me {
+                return Some(chain);
+            }
+            current = waiting.get(&owner)?.clone();
+        }
+        None
+    }
+}
+
+impl Slot {
+    fn new(tid: TypeId, type_name: &'static str) -> Self {
+        Self {
+            tid,
+            type_name,
+            state: Mutex::new(SlotState::Vacant),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn tid(&self) -> TypeId {
+        self.tid
+    }
+
+    pub(crate) fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub(crate) fn is_ready(&self) -> bool {
+        matches!(*lock(&self.state), SlotState::Ready(_))
+    }
+
+    fn downcast<T: 'static>(&self, entry: &Entry) -> Result<Provider<T>, WiringError> {
+        entry
+            .provider
+            .downcast_ref::<Provider<T>>()
+            .cloned()
+            .ok_or(WiringError::TypeMismatch {
+                type_name: self.type_name,
+            })
+    }
+
+    /// Retrieve the stored provider for a rule of the given module.
+    ///
+    /// All resolver modules share the same type map: return an error if the provider was built by the rule
+    /// of another module, instead of silently sharing the provider which was built first.
+    fn resolved_by<T: 'static>(
+        &self,
+        entry: &Entry,
+        module: Option<TypeId>,
+    ) -> Result<Provider<T>, WiringError> {
+        match (entry.module, module) {
+            (Some(built), Some(rule)) if built != rule => Err(WiringError::ConflictingRules {
+                type_name: self.type_name,
+            }),
+            _ => self.downcast::<T>(entry),
+        }
+    }
+
+    /// Retrieve the provider stored in a slot for a rule of the given module, without claiming it
+    pub(crate) fn get<T: 'static>(
+        &self,
+        module: Option<TypeId>,
+    ) -> Result<Option<Provider<T>>, WiringError> {
+        match &*lock(&self.state) {
+            SlotState::Ready(entry) => self.resolved_by::<T>(entry, module).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Store a provider built outside of this type map, unless another one was already stored.
+    ///
+    /// Return the stored provider
+    pub(crate) fn insert<T: 'static>(
+        &self,
+        provider: Provider<T>,
+        deps: Vec<TypeId>,
+        lifetime: Lifetime,
+        duration: Duration,
+        module: Option<TypeId>,
+    ) -> Result<Provider<T>, WiringError> {
+        let mut state = lock(&self.state);
+        if let SlotState::Ready(entry) = &*state {
+            return self.resolved_by::<T>(entry, module);
+        }
+        let (entry, provider) = Entry::new(provider, deps, lifetime);
+        *state = SlotState::Ready(Entry {
+            duration: Some(duration),
+            module,
+            ..entry
+        });
+        self.changed.notify_all();
+        Ok(provider)
+    }
+
+    /// Store the provider of a claimed slot and wake up the waiting threads.
+    ///
+    /// Return the stored provider
+    pub(crate) fn fill<T: 'static>(
+        &self,
+        provider: Provider<T>,
+        deps: Vec<TypeId>,
+        lifetime: Lifetime,
+        duration: Duration,
+        module: Option<TypeId>,
+    ) -> Provider<T> {
+        let (entry, provider) = Entry::new(provider, deps, lifetime);
+        *lock(&self.state) = SlotState::Ready(Entry {
+            duration: Some(duration),
+            module,
+            ..entry
+        });
+        self.changed.notify_all();
+        provider
+    }
+
+    /// Store an inherited entry in a vacant slot
+    fn adopt(&self, entry: Entry) {
+        let mut state = lock(&self.state);
+        if let SlotState::Vacant = *state {
+            *state = SlotState::Ready(entry);
+            self.changed.notify_all();
+        }
+    }
+
+    /// Release a claimed slot after a failed resolution and wake up the waiting threads
+    pub(crate) fn release(&self) {
+        *lock(&self.state) = SlotState::Vacant;
+        self.changed.notify_all();
+    }
+
+    /// Fill a vacant slot.
+    ///
+    /// Return an error if the slot is already in use
+    pub(crate) fn set_if_vacant<T: 'static>(
+        &self,
+        provider: Provider<T>,
+        lifetime: Lifetime,
+    ) -> Result<(), WiringError> {
+        let mut state = lock(&self.state);
+        let SlotState::Vacant = *state else {
+            return Err(WiringError::AlreadyResolved {
+                type_name: self.type_name,
+            });
+        };
+        *state = SlotState::Ready(Entry::overriding(provider, lifetime));
+        Ok(())
+    }
+}